@@ -0,0 +1,148 @@
+use crate::tokenizer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in the source while parsing. Diagnostics never stop the
+/// parser; they are collected when [`ParserOptions::diagnostics`] is set.
+///
+/// [`ParserOptions::diagnostics`]: crate::ParserOptions::diagnostics
+#[derive(Debug, Clone)]
+pub struct Diagnostic<'a> {
+    severity: Severity,
+    code: &'static str,
+    message: String,
+    span: Span<'a>,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span<'a>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span<'a>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// A short, stable, kebab-case identifier for the kind of problem, e.g.
+    /// `deprecated-element`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &Span<'a> {
+        &self.span
+    }
+}
+
+/// Line `line` of `source`, counting from 0 as span positions do, without its
+/// line break. `\n`, `\r` and `\r\n` all end a line.
+pub fn source_line(source: &str, line: usize) -> Option<&str> {
+    let mut rest = source;
+    for _ in 0..line {
+        let end = rest.find(['\n', '\r'])?;
+        let break_length = if rest[end..].starts_with("\r\n") {
+            2
+        } else {
+            1
+        };
+        rest = &rest[end + break_length..];
+    }
+    Some(&rest[..rest.find(['\n', '\r']).unwrap_or(rest.len())])
+}
+
+/// Render `diagnostic` the way compilers do: a header with its severity, code
+/// and message, its location as 1-based line and column, and the source line
+/// with the span underlined by carets. A span running over several lines is
+/// underlined to the end of its first line.
+pub fn render_diagnostic(source: &str, diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let start = &diagnostic.span.range().start;
+    let line_number = (start.line + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+    let mut rendered = format!(
+        "{severity}[{}]: {}\n{gutter}--> {line_number}:{}\n",
+        diagnostic.code,
+        diagnostic.message,
+        start.column + 1
+    );
+    if let Some(line) = source_line(source, start.line) {
+        // keep tabs so that the carets line up with the text above them
+        let padding = line
+            .chars()
+            .take(start.column)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let available = line.chars().count().saturating_sub(start.column);
+        let spanned = source_line(diagnostic.span.source(), 0)
+            .unwrap_or_default()
+            .chars()
+            .count();
+        let carets = "^".repeat(spanned.min(available).max(1));
+        rendered += &format!("{gutter} |\n{line_number} | {line}\n{gutter} | {padding}{carets}\n");
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, ParserOptions};
+
+    #[test]
+    fn source_lines() {
+        let source = "one\ntwo\r\nthree\rfour";
+        let lines = (0..5).map(|i| source_line(source, i)).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![Some("one"), Some("two"), Some("three"), Some("four"), None]
+        );
+        assert_eq!(source_line("", 0), Some(""));
+        assert_eq!(source_line("a\n", 1), Some(""));
+    }
+
+    #[test]
+    fn render_with_carets() {
+        let html = "<html>\n<body>\n\t<p>a <center>b</center></p>\n</body>\n</html>";
+        let options = ParserOptions {
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        parser.parse();
+        let diagnostic = &parser.diagnostics()[0];
+        assert_eq!(
+            render_diagnostic(html, diagnostic),
+            "warning[deprecated-element]: `<center>` is obsolete; use CSS or a current element instead
+ --> 3:7
+  |
+3 | \t<p>a <center>b</center></p>
+  | \t     ^^^^^^^^
+"
+        );
+    }
+}
@@ -0,0 +1,974 @@
+//! Helpers that pull commonly needed data out of a parsed tree.
+
+use std::collections::HashMap;
+
+use crate::{Element, Node, NodeKind, elements, is_html_whitespace};
+
+/// Collect `<meta>` tags into a map keyed by their `name` or, for OpenGraph
+/// tags, `property` attribute, with the `content` attribute as the value.
+///
+/// When a key appears more than once the first tag wins.
+pub fn extract_meta(nodes: &[Node]) -> HashMap<String, String> {
+    let mut meta = HashMap::new();
+    for (element, content) in meta_tags(nodes) {
+        if let Some(key) = element.attr("name").or_else(|| element.attr("property")) {
+            meta.entry(key.to_string())
+                .or_insert_with(|| content.to_string());
+        }
+    }
+    meta
+}
+
+/// The `<meta>` elements in `nodes` that have a `content`, with it.
+fn meta_tags<'n, 'a>(nodes: &'n [Node<'a>]) -> impl Iterator<Item = (&'n Element<'a>, &'a str)> {
+    elements(nodes)
+        .filter(|e| e.is_named("meta"))
+        .filter_map(|e| Some((e, e.attr("content")?)))
+}
+
+/// A page's Open Graph and Twitter Card metadata, as social sites read it to
+/// build link previews.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SocialMeta<'a> {
+    /// `og:title`, or `twitter:title` without it.
+    pub title: Option<&'a str>,
+    /// `og:description`, or `twitter:description` without it.
+    pub description: Option<&'a str>,
+    /// `og:image`, or `twitter:image` without it.
+    pub image: Option<&'a str>,
+    /// `twitter:card`, e.g. `summary_large_image`.
+    pub card: Option<&'a str>,
+    /// Every `og:*` tag as its `property` and `content`, in document order.
+    pub open_graph: Vec<(&'a str, &'a str)>,
+    /// Every `twitter:*` tag as its `name` and `content`, in document order.
+    pub twitter: Vec<(&'a str, &'a str)>,
+}
+
+/// Collect the `og:*` tags, keyed by their `property`, and the `twitter:*`
+/// tags, keyed by their `name`, of a page. Where a key appears more than once
+/// the structured fields take the first.
+pub fn social_metadata<'a>(nodes: &[Node<'a>]) -> SocialMeta<'a> {
+    let mut social = SocialMeta::default();
+    for (element, content) in meta_tags(nodes) {
+        if let Some(property) = element.attr("property").filter(|p| p.starts_with("og:")) {
+            social.open_graph.push((property, content));
+        }
+        if let Some(name) = element.attr("name").filter(|n| n.starts_with("twitter:")) {
+            social.twitter.push((name, content));
+        }
+    }
+    let find = |tags: &[(&'a str, &'a str)], key: &str| {
+        tags.iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, content)| *content)
+    };
+    let either = |key: &str| {
+        find(&social.open_graph, &format!("og:{key}"))
+            .or_else(|| find(&social.twitter, &format!("twitter:{key}")))
+    };
+    SocialMeta {
+        title: either("title"),
+        description: either("description"),
+        image: either("image"),
+        card: find(&social.twitter, "twitter:card"),
+        ..social
+    }
+}
+
+/// The `href` of the document's `<base>` element, which relative URLs in the
+/// document resolve against. Like browsers, only the first `<base>` with an
+/// `href` counts.
+pub fn base_href<'a>(nodes: &[Node<'a>]) -> Option<&'a str> {
+    elements(nodes)
+        .filter(|e| e.is_named("base"))
+        .find_map(|e| e.attr("href"))
+}
+
+/// Whether one of the space-separated `rel` values of `element` is `rel`,
+/// ignoring ASCII case.
+fn has_rel(element: &Element, rel: &str) -> bool {
+    element.attr("rel").is_some_and(|rels| {
+        rels.split_ascii_whitespace()
+            .any(|r| r.eq_ignore_ascii_case(rel))
+    })
+}
+
+/// What SEO tools look at in a page's head. Fields are `None` when the page
+/// doesn't have them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeoInfo<'a> {
+    /// The `href` of the first `<link rel="canonical">`.
+    pub canonical: Option<&'a str>,
+    /// The `content` of the first `<meta name="robots">`, e.g. `noindex, follow`.
+    pub robots: Option<&'a str>,
+    /// The content of the first `<title>`, with surrounding whitespace trimmed.
+    pub title: Option<&'a str>,
+}
+
+/// Collect the canonical URL, robots directives and title of a page.
+pub fn seo_info<'a>(nodes: &[Node<'a>]) -> SeoInfo<'a> {
+    let canonical = elements(nodes)
+        .filter(|e| e.is_named("link") && has_rel(e, "canonical"))
+        .find_map(|e| e.attr("href"));
+    let robots = elements(nodes)
+        .filter(|e| e.is_named("meta"))
+        .filter(|e| {
+            e.attr("name")
+                .is_some_and(|n| n.eq_ignore_ascii_case("robots"))
+        })
+        .find_map(|e| e.attr("content"));
+    let title = elements(nodes)
+        .find(|e| e.is_named("title"))
+        .map(|e| e.inner_source().trim());
+    SeoInfo {
+        canonical,
+        robots,
+        title,
+    }
+}
+
+/// The basics of a page in one go: what it's written in, how it's encoded,
+/// what it's called and where its relative URLs point.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DocumentMeta<'a> {
+    /// The `lang` of the first `<html>` element, unless empty.
+    pub lang: Option<&'a str>,
+    /// The `charset` of a `<meta charset>`, or else the one in the `content`
+    /// of a `<meta http-equiv="Content-Type">`, e.g. `utf-8`.
+    pub charset: Option<&'a str>,
+    /// The content of the first `<title>`, as in [`SeoInfo::title`].
+    pub title: Option<&'a str>,
+    /// The document's base URL, as given by [`base_href`].
+    pub base_href: Option<&'a str>,
+}
+
+/// Collect the language, character encoding, title and base URL of a page.
+pub fn document_meta<'a>(nodes: &[Node<'a>]) -> DocumentMeta<'a> {
+    let lang = elements(nodes)
+        .find(|e| e.is_named("html"))
+        .and_then(|e| e.attr("lang"))
+        .filter(|lang| !lang.is_empty());
+    let charset = elements(nodes)
+        .filter(|e| e.is_named("meta"))
+        .find_map(|e| e.attr("charset"))
+        .map(str::trim)
+        .or_else(|| {
+            meta_tags(nodes)
+                .filter(|(e, _)| {
+                    e.attr("http-equiv")
+                        .is_some_and(|h| h.eq_ignore_ascii_case("content-type"))
+                })
+                .find_map(|(_, content)| charset_of_content_type(content))
+        });
+    DocumentMeta {
+        lang,
+        charset,
+        title: seo_info(nodes).title,
+        base_href: base_href(nodes),
+    }
+}
+
+/// The `charset` parameter of a content type like `text/html; charset=utf-8`.
+fn charset_of_content_type(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(['"', '\'']))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo<'a> {
+    pub src: &'a str,
+    pub alt: Option<&'a str>,
+    pub srcset: Option<&'a str>,
+}
+
+/// Collect every `<img>` with a `src`, in document order.
+pub fn extract_images<'a>(nodes: &[Node<'a>]) -> Vec<ImageInfo<'a>> {
+    elements(nodes)
+        .filter(|e| e.is_named("img"))
+        .filter_map(|e| {
+            Some(ImageInfo {
+                src: e.attr("src")?,
+                alt: e.attr("alt"),
+                srcset: e.attr("srcset"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconKind {
+    /// `rel="icon"`, including the legacy `rel="shortcut icon"`.
+    Icon,
+    /// `rel="apple-touch-icon"`.
+    AppleTouchIcon,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconInfo<'a> {
+    pub kind: IconKind,
+    pub href: &'a str,
+    /// The `sizes` attribute, e.g. `32x32` or `any`.
+    pub sizes: Option<&'a str>,
+}
+
+/// Collect every `<link>` with an `href` declaring a favicon or Apple touch
+/// icon, in document order.
+pub fn extract_icons<'a>(nodes: &[Node<'a>]) -> Vec<IconInfo<'a>> {
+    elements(nodes)
+        .filter(|e| e.is_named("link"))
+        .filter_map(|e| {
+            let kind = if has_rel(e, "icon") {
+                IconKind::Icon
+            } else if has_rel(e, "apple-touch-icon") {
+                IconKind::AppleTouchIcon
+            } else {
+                return None;
+            };
+            Some(IconInfo {
+                kind,
+                href: e.attr("href")?,
+                sizes: e.attr("sizes"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Script,
+    Link,
+    Image,
+}
+
+/// A resource a page loads, with its subresource integrity settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource<'a> {
+    pub kind: ResourceKind,
+    pub url: &'a str,
+    pub integrity: Option<&'a str>,
+    pub crossorigin: Option<&'a str>,
+}
+
+/// Collect every `<script src>`, `<link href>` and `<img src>`, in document
+/// order, for auditing subresource integrity coverage.
+pub fn external_resources<'a>(nodes: &[Node<'a>]) -> Vec<Resource<'a>> {
+    elements(nodes)
+        .filter_map(|e| {
+            let (kind, url) = if e.is_named("script") {
+                (ResourceKind::Script, e.attr("src")?)
+            } else if e.is_named("link") {
+                (ResourceKind::Link, e.attr("href")?)
+            } else if e.is_named("img") {
+                (ResourceKind::Image, e.attr("src")?)
+            } else {
+                return None;
+            };
+            Some(Resource {
+                kind,
+                url,
+                integrity: e.attr("integrity"),
+                crossorigin: e.attr("crossorigin"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormField<'a> {
+    pub name: Option<&'a str>,
+    /// The `type` of an `<input>` (`text` if it has none), or `select` or
+    /// `textarea` for those elements.
+    pub field_type: &'a str,
+    /// The value the field starts out with, if any.
+    pub value: Option<&'a str>,
+}
+
+/// Collect the `<input>`, `<select>` and `<textarea>` elements nested in
+/// `form`, in document order, with their initial values.
+///
+/// A `<textarea>`'s value is its content. A `<select>`'s is that of its first
+/// `selected` option, or of its first option if none is selected, where an
+/// option without a `value` attribute has its text as value.
+pub fn extract_form_fields<'a>(form: &Element<'a>) -> Vec<FormField<'a>> {
+    form.descendant_elements()
+        .filter_map(|e| {
+            let (field_type, value) = if e.is_named("input") {
+                (e.attr("type").unwrap_or("text"), e.attr("value"))
+            } else if e.is_named("textarea") {
+                ("textarea", Some(e.inner_source()))
+            } else if e.is_named("select") {
+                let options = e
+                    .descendant_elements()
+                    .filter(|o| o.is_named("option"))
+                    .collect::<Vec<_>>();
+                let selected = options
+                    .iter()
+                    .find(|o| o.attr("selected").is_some())
+                    .or(options.first());
+                let value =
+                    selected.map(|o| o.attr("value").unwrap_or_else(|| o.inner_source().trim()));
+                ("select", value)
+            } else {
+                return None;
+            };
+            Some(FormField {
+                name: e.attr("name"),
+                field_type,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// How a `<form>` submits, with the fields it submits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormInfo<'a> {
+    pub action: Option<&'a str>,
+    /// The `method` attribute as written, or `get` if it's missing.
+    pub method: &'a str,
+    /// The `enctype` attribute as written, or
+    /// `application/x-www-form-urlencoded` if it's missing.
+    pub enctype: &'a str,
+    /// The fields as [`extract_form_fields`] collects them.
+    pub fields: Vec<FormField<'a>>,
+}
+
+impl<'a> Element<'a> {
+    /// Describe this element as a form, or `None` if it isn't a `<form>`.
+    pub fn as_form(&self) -> Option<FormInfo<'a>> {
+        if !self.is_named("form") {
+            return None;
+        }
+        Some(FormInfo {
+            action: self.attr("action"),
+            method: self.attr("method").unwrap_or("get"),
+            enctype: self
+                .attr("enctype")
+                .unwrap_or("application/x-www-form-urlencoded"),
+            fields: extract_form_fields(self),
+        })
+    }
+}
+
+impl Element<'_> {
+    /// The image candidates in the `srcset` attribute, as `(url, descriptor)`
+    /// pairs where the descriptor is a width like `640w` or a density like
+    /// `2x`, or `None` if the candidate has none.
+    ///
+    /// Candidates are split as the HTML spec does: a URL runs up to
+    /// whitespace, so commas inside it, as in data URIs, don't split it, and
+    /// commas ending it separate it from the next candidate.
+    pub fn srcset(&self) -> Vec<(String, Option<String>)> {
+        let mut candidates = vec![];
+        let Some(mut rest) = self.attr("srcset") else {
+            return candidates;
+        };
+        loop {
+            rest = rest.trim_start_matches(|c| is_html_whitespace(c) || c == ',');
+            if rest.is_empty() {
+                return candidates;
+            }
+            let url_end = rest.find(is_html_whitespace).unwrap_or(rest.len());
+            let url = &rest[..url_end];
+            rest = &rest[url_end..];
+            let trimmed = url.trim_end_matches(',');
+            if trimmed.len() < url.len() {
+                candidates.push((trimmed.to_string(), None));
+                continue;
+            }
+            // the descriptor runs up to a comma outside parentheses
+            let mut depth = 0usize;
+            let end = rest
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    c == ',' && depth == 0
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            let descriptor = rest[..end].trim_matches(is_html_whitespace);
+            rest = &rest[end..];
+            candidates.push((
+                url.to_string(),
+                (!descriptor.is_empty()).then(|| descriptor.to_string()),
+            ));
+        }
+    }
+}
+
+/// A `<source>` in a `<picture>`, one of the images a browser picks from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureSource<'a> {
+    /// The candidates in its `srcset`, as [`Element::srcset`] gives them.
+    pub srcset: Vec<(String, Option<String>)>,
+    /// The media query it applies under, e.g. `(min-width: 800px)`.
+    pub media: Option<&'a str>,
+    /// The MIME type of its images, e.g. `image/webp`.
+    pub source_type: Option<&'a str>,
+}
+
+/// The images a `<picture>` offers: its sources in order of preference, and
+/// the `<img>` shown when none of them applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureInfo<'a> {
+    pub sources: Vec<PictureSource<'a>>,
+    /// The fallback `<img>`, if it has a `src`.
+    pub img: Option<ImageInfo<'a>>,
+}
+
+impl<'a> Element<'a> {
+    /// Describe this element as a responsive image set, or `None` if it isn't
+    /// a `<picture>`. Only the `<source>` and `<img>` children of the picture
+    /// count, as in the browser.
+    pub fn as_picture(&self) -> Option<PictureInfo<'a>> {
+        if !self.is_named("picture") {
+            return None;
+        }
+        let sources = self
+            .child_elements()
+            .filter(|e| e.is_named("source"))
+            .map(|e| PictureSource {
+                srcset: e.srcset(),
+                media: e.attr("media"),
+                source_type: e.attr("type"),
+            })
+            .collect();
+        let img = self
+            .child_elements()
+            .find(|e| e.is_named("img"))
+            .and_then(|e| {
+                Some(ImageInfo {
+                    src: e.attr("src")?,
+                    alt: e.attr("alt"),
+                    srcset: e.attr("srcset"),
+                })
+            });
+        Some(PictureInfo { sources, img })
+    }
+}
+
+/// A heading in a page's table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry<'a> {
+    /// 1 for `<h1>` through 6 for `<h6>`.
+    pub level: u8,
+    /// The text inside the heading, with runs of whitespace collapsed to a
+    /// single space and the ends trimmed.
+    pub text: String,
+    pub id: Option<&'a str>,
+}
+
+/// Collect the `<h1>`–`<h6>` headings in document order, for building a table
+/// of contents.
+pub fn build_toc<'a>(nodes: &[Node<'a>]) -> Vec<TocEntry<'a>> {
+    elements(nodes)
+        .filter_map(|e| {
+            let &[b'h' | b'H', digit @ b'1'..=b'6'] = e.name().as_bytes() else {
+                return None;
+            };
+            let level = digit - b'0';
+            Some(TocEntry {
+                level,
+                text: collapsed_text(e),
+                id: e.attr("id"),
+            })
+        })
+        .collect()
+}
+
+/// The text nested in `element`, with runs of whitespace collapsed.
+fn collapsed_text(element: &Element) -> String {
+    let mut text = String::new();
+    let mut stack = element.children().iter().rev().collect::<Vec<_>>();
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            NodeKind::Text(token) => text.push_str(token.span().source()),
+            NodeKind::Element(child) => stack.extend(child.children().iter().rev()),
+            _ => {}
+        }
+    }
+    text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Every inline event handler attribute, like `onclick` or `onerror`, with
+/// the element it is on, its name as written and its value, in document
+/// order. These run script, so they are what to audit for XSS in untrusted
+/// HTML.
+pub fn find_inline_handlers<'n, 'a>(
+    nodes: &'n [Node<'a>],
+) -> Vec<(&'n Element<'a>, &'a str, &'a str)> {
+    nodes
+        .iter()
+        .filter_map(|node| match &node.kind {
+            NodeKind::Element(element) => Some(element),
+            _ => None,
+        })
+        .flat_map(|element| element.all_attributes())
+        .filter(|(_, attribute)| {
+            let name = attribute.name_text();
+            name.len() > 2 && name.get(..2).is_some_and(|p| p.eq_ignore_ascii_case("on"))
+        })
+        .map(|(element, attribute)| (element, attribute.name_text(), attribute.value_text()))
+        .collect()
+}
+
+/// The content of every `<script>` data block whose `type` is `mime_type`
+/// (compared ignoring ASCII case), in document order. See
+/// [`Element::is_data_block`](crate::Element::is_data_block).
+pub fn extract_data_blocks<'a>(nodes: &[Node<'a>], mime_type: &str) -> Vec<&'a str> {
+    elements(nodes)
+        .filter(|e| e.is_data_block())
+        .filter(|e| {
+            e.attr("type")
+                .is_some_and(|t| t.trim().eq_ignore_ascii_case(mime_type))
+        })
+        .map(|e| e.inner_source())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeKind, Parser};
+
+    #[test]
+    fn meta_name_and_property() {
+        let html = "<html><head>
+            <meta charset=\"utf-8\">
+            <meta name=\"description\" content=\"A page about things\">
+            <meta property=\"og:title\" content=\"Things\">
+            <meta name=\"description\" content=\"ignored duplicate\">
+            </head><body></body></html>";
+        let nodes = Parser::new(html).parse();
+        let meta = extract_meta(&nodes);
+        let expected = HashMap::from([
+            ("description".to_string(), "A page about things".to_string()),
+            ("og:title".to_string(), "Things".to_string()),
+        ]);
+        assert_eq!(meta, expected);
+    }
+
+    #[test]
+    fn open_graph_and_twitter() {
+        let html = "<html><head>
+            <meta property=\"og:title\" content=\"Things\">
+            <meta property=\"og:image\" content=\"https://example.com/og.png\">
+            <meta property=\"og:type\" content=\"article\">
+            <meta name=\"twitter:card\" content=\"summary_large_image\">
+            <meta name=\"twitter:title\" content=\"Things on Twitter\">
+            <meta name=\"twitter:description\" content=\"All about things\">
+            <meta name=\"og:description\" content=\"not a property\">
+            <meta name=\"description\" content=\"Plain description\">
+            </head><body></body></html>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            social_metadata(&nodes),
+            SocialMeta {
+                title: Some("Things"),
+                description: Some("All about things"),
+                image: Some("https://example.com/og.png"),
+                card: Some("summary_large_image"),
+                open_graph: vec![
+                    ("og:title", "Things"),
+                    ("og:image", "https://example.com/og.png"),
+                    ("og:type", "article"),
+                ],
+                twitter: vec![
+                    ("twitter:card", "summary_large_image"),
+                    ("twitter:title", "Things on Twitter"),
+                    ("twitter:description", "All about things"),
+                ],
+            }
+        );
+        assert_eq!(
+            social_metadata(&Parser::new("<p>none</p>").parse()),
+            SocialMeta::default()
+        );
+    }
+
+    #[test]
+    fn base_href_of_document() {
+        let html = "<html><head><base target=\"_blank\"><base href=\"https://example.com/\"><base href=\"/ignored/\"></head><body><a href=\"page\">x</a></body></html>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(base_href(&nodes), Some("https://example.com/"));
+
+        let nodes =
+            Parser::new("<html><head></head><body><a href=\"page\">x</a></body></html>").parse();
+        assert_eq!(base_href(&nodes), None);
+    }
+
+    #[test]
+    fn meta_of_document() {
+        let html = "<!DOCTYPE html><html lang=\"en-GB\"><head>
+            <meta charset=\"UTF-8\">
+            <title> Things </title>
+            <base href=\"https://example.com/docs/\">
+            </head><body><p lang=\"fr\">Bonjour</p></body></html>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            document_meta(&nodes),
+            DocumentMeta {
+                lang: Some("en-GB"),
+                charset: Some("UTF-8"),
+                title: Some("Things"),
+                base_href: Some("https://example.com/docs/"),
+            }
+        );
+
+        let html = "<html lang=\"\"><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=iso-8859-1\"></head></html>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            document_meta(&nodes),
+            DocumentMeta {
+                charset: Some("iso-8859-1"),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn inline_handlers() {
+        let html = "<body onload=\"init()\"><p>Hi <img src=x OnError=\"alert(1)\"></p><a href=# onclick='go()' on=2>x</a></body><div onmouseover=h></div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            find_inline_handlers(&nodes)
+                .iter()
+                .map(|(element, name, value)| (element.tag_name(), *name, *value))
+                .collect::<Vec<_>>(),
+            vec![
+                ("body", "onload", "init()"),
+                ("img", "OnError", "alert(1)"),
+                ("a", "onclick", "go()"),
+                ("div", "onmouseover", "h"),
+            ]
+        );
+        assert!(find_inline_handlers(&Parser::new("<p class=on>x</p>").parse()).is_empty());
+        let nodes = Parser::new("<p oé=1 é=2 oné=3>x</p>").parse();
+        assert_eq!(
+            find_inline_handlers(&nodes)
+                .iter()
+                .map(|(_, name, value)| (*name, *value))
+                .collect::<Vec<_>>(),
+            vec![("oné", "3")]
+        );
+    }
+
+    #[test]
+    fn images() {
+        let html = "<body><img src=\"/logo.png\" alt=\"Logo\"><p><img src=hero.jpg srcset=\"hero-2x.jpg 2x\" alt=\"\"></p><img alt=\"no source\"><img src='/plain.gif'></body>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            extract_images(&nodes),
+            vec![
+                ImageInfo {
+                    src: "/logo.png",
+                    alt: Some("Logo"),
+                    srcset: None
+                },
+                ImageInfo {
+                    src: "hero.jpg",
+                    alt: Some(""),
+                    srcset: Some("hero-2x.jpg 2x")
+                },
+                ImageInfo {
+                    src: "/plain.gif",
+                    alt: None,
+                    srcset: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn icons() {
+        let html = "<head><link rel=\"shortcut icon\" href=\"/favicon.ico\">
+            <link rel=icon type=\"image/png\" sizes=32x32 href=\"/icon-32.png\">
+            <link rel=stylesheet href=/style.css>
+            <link rel=\"Apple-Touch-Icon\" sizes=\"180x180\" href=\"/apple.png\">
+            <link rel=icon></head>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            extract_icons(&nodes),
+            vec![
+                IconInfo {
+                    kind: IconKind::Icon,
+                    href: "/favicon.ico",
+                    sizes: None
+                },
+                IconInfo {
+                    kind: IconKind::Icon,
+                    href: "/icon-32.png",
+                    sizes: Some("32x32")
+                },
+                IconInfo {
+                    kind: IconKind::AppleTouchIcon,
+                    href: "/apple.png",
+                    sizes: Some("180x180")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn data_blocks_by_type() {
+        let html = "<body><script type=\"text/template\"><li class=item>{{ name }}</li></script>
+            <script>let a = '<b>';</script>
+            <script type=module>import x from './x.js';</script>
+            <script type=\"application/json\">{\"a\": \"</p>\"}</script></body>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            extract_data_blocks(&nodes, "text/template"),
+            vec!["<li class=item>{{ name }}</li>"]
+        );
+        assert_eq!(
+            extract_data_blocks(&nodes, "APPLICATION/JSON"),
+            vec!["{\"a\": \"</p>\"}"]
+        );
+        assert!(extract_data_blocks(&nodes, "module").is_empty());
+        assert!(extract_data_blocks(&nodes, "").is_empty());
+    }
+
+    #[test]
+    fn form_fields() {
+        let html = "<form action=/signup>
+            <input name=user>
+            <input type=password name=pass value=\"secret\">
+            <label>Plan <select name=plan><option>Free</option><option value=pro selected>Pro</option></select></label>
+            <select name=size><option> small </option><option>large</option></select>
+            <textarea name=bio>Hello <b>there</b></textarea>
+            <button>Go</button>
+        </form>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(form) = &nodes[0].kind else {
+            panic!("Expected form, got: {:?}", &nodes[0].kind);
+        };
+        let field = |name, field_type, value| FormField {
+            name: Some(name),
+            field_type,
+            value,
+        };
+        assert_eq!(
+            extract_form_fields(form),
+            vec![
+                field("user", "text", None),
+                field("pass", "password", Some("secret")),
+                field("plan", "select", Some("pro")),
+                field("size", "select", Some("small")),
+                field("bio", "textarea", Some("Hello <b>there</b>")),
+            ]
+        );
+    }
+
+    #[test]
+    fn form_info() {
+        let html = "<div><form action=\"/submit\" method=\"post\" enctype=\"multipart/form-data\">
+            <input name=title><input type=file name=upload>
+        </form><form><input name=q></form></div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(div.as_form(), None);
+        let forms = div.child_elements().collect::<Vec<_>>();
+        assert_eq!(
+            forms[0].as_form(),
+            Some(FormInfo {
+                action: Some("/submit"),
+                method: "post",
+                enctype: "multipart/form-data",
+                fields: vec![
+                    FormField {
+                        name: Some("title"),
+                        field_type: "text",
+                        value: None
+                    },
+                    FormField {
+                        name: Some("upload"),
+                        field_type: "file",
+                        value: None
+                    },
+                ],
+            })
+        );
+        let search = forms[1].as_form().expect("a form");
+        assert_eq!(
+            (search.action, search.method, search.enctype),
+            (None, "get", "application/x-www-form-urlencoded")
+        );
+        assert_eq!(search.fields.len(), 1);
+    }
+
+    #[test]
+    fn picture_sources() {
+        let html = "<picture>
+            <source srcset=\"hero.avif 1x, hero@2x.avif 2x\" type=image/avif>
+            <source media=\"(max-width: 600px)\" srcset=\"hero-small.jpg\">
+            <img src=hero.jpg alt=\"A hero\">
+        </picture>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(picture) = &nodes[0].kind else {
+            panic!("Expected picture, got: {:?}", &nodes[0].kind);
+        };
+        let candidate =
+            |url: &str, descriptor: Option<&str>| (url.to_string(), descriptor.map(str::to_string));
+        assert_eq!(
+            picture.as_picture(),
+            Some(PictureInfo {
+                sources: vec![
+                    PictureSource {
+                        srcset: vec![
+                            candidate("hero.avif", Some("1x")),
+                            candidate("hero@2x.avif", Some("2x")),
+                        ],
+                        media: None,
+                        source_type: Some("image/avif"),
+                    },
+                    PictureSource {
+                        srcset: vec![candidate("hero-small.jpg", None)],
+                        media: Some("(max-width: 600px)"),
+                        source_type: None,
+                    },
+                ],
+                img: Some(ImageInfo {
+                    src: "hero.jpg",
+                    alt: Some("A hero"),
+                    srcset: None,
+                }),
+            })
+        );
+        let img = picture.child_elements().last().unwrap();
+        assert_eq!(img.as_picture(), None);
+    }
+
+    #[test]
+    fn srcset_candidates() {
+        let html = "<img srcset=\"small.jpg 480w, large.jpg  1080w,\n medium.jpg\">
+            <img srcset=\"a.png, b.png 2x,c.png 1.5x\">
+            <img srcset=\"data:image/png;base64,iVBO,R0lG 1x, /x,y.png,, fallback.png\">
+            <img src=plain.png>";
+        let nodes = Parser::new(html).parse();
+        let images = elements(&nodes).map(|e| e.srcset()).collect::<Vec<_>>();
+        let candidate =
+            |url: &str, descriptor: Option<&str>| (url.to_string(), descriptor.map(str::to_string));
+        assert_eq!(
+            images,
+            vec![
+                vec![
+                    candidate("small.jpg", Some("480w")),
+                    candidate("large.jpg", Some("1080w")),
+                    candidate("medium.jpg", None),
+                ],
+                vec![
+                    candidate("a.png", None),
+                    candidate("b.png", Some("2x")),
+                    candidate("c.png", Some("1.5x")),
+                ],
+                vec![
+                    candidate("data:image/png;base64,iVBO,R0lG", Some("1x")),
+                    candidate("/x,y.png", None),
+                    candidate("fallback.png", None),
+                ],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn table_of_contents() {
+        let html = "<body><h1 id=top>Guide</h1>
+            <section><h2 id=install>Installing <code>pkg</code></h2>
+                <p>text</p>
+                <h3>On
+                    Linux</h3>
+            </section>
+            <header><hgroup><h2 id=usage>Usage</h2></hgroup></header>
+            <h7>not a heading</h7><h+1>nor</h+1><h01>this</h01></body>";
+        let nodes = Parser::new(html).parse();
+        let entry = |level, text: &str, id| TocEntry {
+            level,
+            text: text.to_string(),
+            id,
+        };
+        assert_eq!(
+            build_toc(&nodes),
+            vec![
+                entry(1, "Guide", Some("top")),
+                entry(2, "Installing pkg", Some("install")),
+                entry(3, "On Linux", None),
+                entry(2, "Usage", Some("usage")),
+            ]
+        );
+    }
+
+    #[test]
+    fn seo_info_from_head() {
+        let html = "<html><head>
+            <title> Things | Example </title>
+            <link rel=stylesheet href=/style.css>
+            <link rel=\"Canonical\" href=\"https://example.com/things\">
+            <meta name=ROBOTS content=\"noindex, follow\">
+        </head><body><svg><title>icon</title></svg></body></html>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            seo_info(&nodes),
+            SeoInfo {
+                canonical: Some("https://example.com/things"),
+                robots: Some("noindex, follow"),
+                title: Some("Things | Example"),
+            }
+        );
+
+        let nodes = Parser::new("<p>no head</p>").parse();
+        assert_eq!(
+            seo_info(&nodes),
+            SeoInfo {
+                canonical: None,
+                robots: None,
+                title: None,
+            }
+        );
+    }
+
+    #[test]
+    fn external_resources_with_integrity() {
+        let html = "<html><head>
+            <script src=\"https://cdn.example/lib.js\" integrity=\"sha384-abc\" crossorigin=anonymous></script>
+            <script>inline()</script>
+            <link rel=stylesheet href=\"/site.css\">
+            <link rel=stylesheet href=\"https://cdn.example/x.css\" integrity=\"sha256-def\" crossorigin>
+        </head><body><img src=logo.png><img alt=missing></body></html>";
+        let nodes = Parser::new(html).parse();
+        let resource = |kind, url, integrity, crossorigin| Resource {
+            kind,
+            url,
+            integrity,
+            crossorigin,
+        };
+        assert_eq!(
+            external_resources(&nodes),
+            vec![
+                resource(
+                    ResourceKind::Script,
+                    "https://cdn.example/lib.js",
+                    Some("sha384-abc"),
+                    Some("anonymous")
+                ),
+                resource(ResourceKind::Link, "/site.css", None, None),
+                resource(
+                    ResourceKind::Link,
+                    "https://cdn.example/x.css",
+                    Some("sha256-def"),
+                    Some("")
+                ),
+                resource(ResourceKind::Image, "logo.png", None, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Classifying the source for syntax highlighting.
+
+use std::ops::Range;
+
+use crate::{
+    is_html_whitespace,
+    tokenizer::{TokenKind, Tokenizer, TokenizerOptions},
+};
+
+/// What a range of the source is, for picking its highlighting color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    /// The name in a start or end tag.
+    TagName,
+    AttributeName,
+    /// An attribute value, including its quotes.
+    AttributeValue,
+    /// Text content, and whitespace between the parts of a tag.
+    Text,
+    /// A comment, including its `<!--` and `-->`. Doctypes and processing
+    /// instructions are classed as comments too.
+    Comment,
+    /// One of the `<`, `>`, `/` and `=` characters that make up a tag.
+    Delimiter,
+}
+
+/// Add `range` to `ranges`, merging it into the previous range when that has
+/// the same class. Delimiters are never merged.
+fn push(ranges: &mut Vec<(Range<usize>, TokenClass)>, range: Range<usize>, class: TokenClass) {
+    if range.is_empty() {
+        return;
+    }
+    match ranges.last_mut() {
+        Some((last, last_class))
+            if *last_class == class
+                && class != TokenClass::Delimiter
+                && last.end == range.start =>
+        {
+            last.end = range.end;
+        }
+        _ => ranges.push((range, class)),
+    }
+}
+
+/// Split `range`, markup around a token, into delimiters, whitespace and
+/// `class` for anything else, such as the quotes around an attribute value.
+fn push_markup(
+    ranges: &mut Vec<(Range<usize>, TokenClass)>,
+    source: &str,
+    range: Range<usize>,
+    class: TokenClass,
+) {
+    for (i, c) in source[range.clone()].char_indices() {
+        let i = range.start + i;
+        let class = if matches!(c, '<' | '>' | '/' | '=') {
+            TokenClass::Delimiter
+        } else if is_html_whitespace(c) {
+            TokenClass::Text
+        } else {
+            class
+        };
+        push(ranges, i..i + c.len_utf8(), class);
+    }
+}
+
+/// Classify all of `source` for syntax highlighting, as byte ranges in order
+/// that together cover the whole input.
+///
+/// Tags are split into their name, attribute names and values, and the `<`,
+/// `>`, `/` and `=` delimiters, each delimiter as a range of its own. The
+/// content of `<script>`, `<style>` and the other raw text elements is text.
+pub fn highlight_tokens(source: &str) -> Vec<(Range<usize>, TokenClass)> {
+    let mut tokenizer = Tokenizer::with_options(source, TokenizerOptions::default());
+    let mut ranges = vec![];
+    loop {
+        let start = tokenizer.here().byte_range().start;
+        let Some(token) = tokenizer.next() else {
+            break;
+        };
+        let end = tokenizer.here().byte_range().start;
+        let core = token.span().byte_range();
+        let class = match token.kind() {
+            TokenKind::TagName { .. } | TokenKind::TagEnd { .. } => TokenClass::TagName,
+            TokenKind::AttributeName { .. } => TokenClass::AttributeName,
+            TokenKind::AttributeValue { .. } => TokenClass::AttributeValue,
+            TokenKind::OpeningTagEnd | TokenKind::SelfClosingTagEnd => TokenClass::Delimiter,
+            TokenKind::Text { .. } | TokenKind::Whitespace { .. } => TokenClass::Text,
+            TokenKind::CData { .. }
+            | TokenKind::Comment { .. }
+            | TokenKind::Doctype { .. }
+            | TokenKind::ProcessingInstruction { .. } => {
+                // the end of a previous tag may come before the `<` that
+                // opens this one
+                let opener = source[start..core.start]
+                    .rfind('<')
+                    .map_or(core.start, |i| start + i);
+                push_markup(&mut ranges, source, start..opener, TokenClass::Text);
+                let class = if matches!(token.kind(), TokenKind::CData { .. }) {
+                    TokenClass::Text
+                } else {
+                    TokenClass::Comment
+                };
+                push(&mut ranges, opener..end, class);
+                continue;
+            }
+        };
+        push_markup(&mut ranges, source, start..core.start, class);
+        if class == TokenClass::Delimiter {
+            push_markup(&mut ranges, source, core.clone(), class);
+        } else {
+            push(&mut ranges, core.clone(), class);
+        }
+        push_markup(&mut ranges, source, core.end..end, class);
+    }
+    let end = tokenizer.here().byte_range().start;
+    push(&mut ranges, end..source.len(), TokenClass::Text);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_of_small_document() {
+        let html = "<!DOCTYPE html>\n<p class=\"a b\" hidden>T&amp;C <br/></p>\n<!-- c --><script>a</b></script>";
+        let ranges = highlight_tokens(html);
+        let mut end = 0;
+        for (range, _) in &ranges {
+            assert_eq!(range.start, end);
+            end = range.end;
+        }
+        assert_eq!(end, html.len());
+
+        use TokenClass::*;
+        assert_eq!(
+            ranges
+                .iter()
+                .map(|(range, class)| (&html[range.clone()], *class))
+                .collect::<Vec<_>>(),
+            vec![
+                ("<!DOCTYPE html>", Comment),
+                ("\n", Text),
+                ("<", Delimiter),
+                ("p", TagName),
+                (" ", Text),
+                ("class", AttributeName),
+                ("=", Delimiter),
+                ("\"a b\"", AttributeValue),
+                (" ", Text),
+                ("hidden", AttributeName),
+                (">", Delimiter),
+                ("T&amp;C ", Text),
+                ("<", Delimiter),
+                ("br", TagName),
+                ("/", Delimiter),
+                (">", Delimiter),
+                ("<", Delimiter),
+                ("/", Delimiter),
+                ("p", TagName),
+                (">", Delimiter),
+                ("\n", Text),
+                ("<!-- c -->", Comment),
+                ("<", Delimiter),
+                ("script", TagName),
+                (">", Delimiter),
+                ("a</b>", Text),
+                ("<", Delimiter),
+                ("/", Delimiter),
+                ("script", TagName),
+                (">", Delimiter),
+            ]
+        );
+    }
+}
@@ -1,21 +1,194 @@
-use std::fmt::Display;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
 
-use tokenizer::{Token, TokenKind, Tokenizer};
+use tokenizer::{Token, TokenKind, Tokenizer, TokenizerOptions};
 
+pub use diagnostic::{Diagnostic, Severity, render_diagnostic, source_line};
+pub use extract::{
+    DocumentMeta, FormField, FormInfo, IconInfo, IconKind, ImageInfo, PictureInfo, PictureSource,
+    Resource, ResourceKind, SeoInfo, SocialMeta, TocEntry, base_href, build_toc, document_meta,
+    external_resources, extract_data_blocks, extract_form_fields, extract_icons, extract_images,
+    extract_meta, find_inline_handlers, seo_info, social_metadata,
+};
+pub use highlight::{TokenClass, highlight_tokens};
+pub use owned::{OwnedElement, OwnedNode};
+pub use reparse::reparse_region;
+pub use select::select;
+pub use serialize::{CommentPolicy, SelfClosingStyle, SerializeOptions};
+pub use tag_ranges::{TagKind, TagRange, tag_ranges};
+pub use tokenizer::{Position, Span, is_html_whitespace};
+pub use validate::{find_replacement_chars, validate_nesting};
+
+mod diagnostic;
+mod extract;
+mod highlight;
+mod owned;
+mod path;
+mod reparse;
+mod select;
+mod serialize;
+mod tag_ranges;
 mod tokenizer;
+mod validate;
 
 pub struct Parser<'a> {
     tokenizer: Tokenizer<'a>,
+    options: ParserOptions,
 
     open_tag_stack: Vec<&'a str>,
+    namespace: Namespace,
+    /// A token (and the span where it starts) that ended the element being
+    /// parsed and still has to be handled by its parent.
+    pending: Option<(Span<'a>, Token<'a>)>,
+    /// Where the content of the innermost element that stopped parsing ended.
+    content_end: Span<'a>,
+    /// Whether the `<!DOCTYPE>` at the start of the document has been seen.
+    seen_doctype: bool,
+    diagnostics: Vec<Diagnostic<'a>>,
+}
+
+/// An element whose start tag has been parsed and whose content is being
+/// parsed.
+struct OpenElement<'a> {
+    /// An empty span where the start tag begins.
+    start: Span<'a>,
+    name: &'a str,
+    tag_name: Token<'a>,
+    /// The attributes kept, followed by the duplicates dropped from them.
+    attributes: Vec<Attribute<'a>>,
+    attribute_count: usize,
+    namespace: Namespace,
+    /// The namespace to go back to once the element is closed.
+    parent_namespace: Namespace,
+    open_tag: Span<'a>,
+    /// The nodes before this element in its parent.
+    siblings: Vec<Node<'a>>,
+    /// Whether this element is kept in the tree, rather than dropped by the
+    /// filter of [`Parser::parse_filtered`].
+    kept: bool,
+}
+
+/// Decides from its tag name and attributes whether an element is kept, for
+/// [`Parser::parse_filtered`].
+type ElementFilter<'f> = &'f dyn Fn(&str, &[Attribute]) -> bool;
+
+impl OpenElement<'_> {
+    /// Whether a `name` end tag closes this element. HTML names are compared
+    /// ignoring ASCII case, names in foreign content exactly.
+    fn is_closed_by(&self, name: &str) -> bool {
+        self.name == name
+            || self.namespace == Namespace::Html && self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    /// Recover from more authoring errors than the default parser does:
+    /// inline formatting elements (`b`, `i`, `span`, ...) left open are closed
+    /// when a block element (`div`, `p`, `ul`, ...) starts inside them.
+    pub lenient: bool,
+    /// Accept `{...}` and `{{...}}` template expressions as unquoted
+    /// attribute values, as used by frontend frameworks.
+    pub template_expressions: bool,
+    /// Collect [`Diagnostic`]s about questionable markup while parsing,
+    /// available from [`Parser::diagnostics`].
+    pub diagnostics: bool,
+    /// Keep at most this many attributes per element, dropping the rest
+    /// with a `too-many-attributes` diagnostic. Bounds memory use on
+    /// adversarial input. Duplicate attributes, kept for
+    /// [`Element::raw_attributes`], count towards the limit.
+    pub max_attributes_per_element: Option<usize>,
+    /// Report markup that the parser can recover from but that isn't valid
+    /// HTML, such as a bare `<` in text, as error diagnostics. Implies
+    /// `diagnostics`.
+    ///
+    /// This includes mismatched end tags: an end tag closes the nearest open
+    /// element with its name along with any elements still open inside it,
+    /// as in `<b><i></b>`, and an end tag with no open element to close is
+    /// ignored. Strict parsing recovers the same way, but reports each
+    /// element closed like that and each ignored end tag.
+    pub strict: bool,
+    /// Lowercase tag names of HTML elements. Names in `<svg>` and `<math>`
+    /// content are case-sensitive and kept as written.
+    pub normalize_tag_case: bool,
+    /// Lowercase attribute names on HTML elements, leaving those in `<svg>`
+    /// and `<math>` content as written.
+    pub normalize_attr_case: bool,
+    /// Rename elements while parsing: called with each tag name as written,
+    /// start and end tags alike, it returns the name to parse the element
+    /// as, like `div` for an obsolete `center`. [`Element::name`] and the
+    /// serializer use the new name, while [`Element::tag_name`] and the
+    /// element's span keep the source as written.
+    ///
+    /// The tokenizer still goes by the name as written, so renaming an
+    /// element to or from a raw text element like `script` doesn't change
+    /// how its content is read.
+    pub tag_rewrite: Option<fn(&str) -> &str>,
+    /// Keep attributes that repeat the name of an earlier one on the same
+    /// element in [`Element::attributes`]. By default they are dropped, as
+    /// browsers do, keeping the first, and are only available from
+    /// [`Element::raw_attributes`]. Names are compared ignoring ASCII case on
+    /// HTML elements and exactly in `<svg>` and `<math>` content.
+    pub keep_duplicate_attributes: bool,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(source: &'a str) -> Self {
-        let tokenizer = Tokenizer::new(source.trim());
+        Self::with_options(source, ParserOptions::default())
+    }
+
+    pub fn with_options(source: &'a str, options: ParserOptions) -> Self {
+        // leading whitespace is skipped rather than sliced off so that spans
+        // stay relative to `source`
+        let tokenizer_options = TokenizerOptions {
+            template_expressions: options.template_expressions,
+            ..Default::default()
+        };
+        let mut tokenizer = Tokenizer::with_options(
+            source.trim_end_matches(is_html_whitespace),
+            tokenizer_options,
+        );
+        tokenizer.consume_whitespace();
+        Self::with_tokenizer(tokenizer, options)
+    }
+
+    /// A parser for the top level of a document, reading from wherever
+    /// `tokenizer` is.
+    fn with_tokenizer(tokenizer: Tokenizer<'a>, options: ParserOptions) -> Self {
+        let content_end = tokenizer.here();
         Self {
             tokenizer,
+            options,
             open_tag_stack: vec![],
+            namespace: Namespace::Html,
+            pending: None,
+            content_end,
+            seen_doctype: false,
+            diagnostics: vec![],
+        }
+    }
+
+    /// The diagnostics collected so far. Always empty unless
+    /// [`ParserOptions::diagnostics`] is set.
+    pub fn diagnostics(&self) -> &[Diagnostic<'a>] {
+        &self.diagnostics
+    }
+
+    /// `name` as renamed by [`ParserOptions::tag_rewrite`].
+    fn rewrite(&self, name: &'a str) -> &'a str {
+        self.options
+            .tag_rewrite
+            .map_or(name, |rewrite| rewrite(name))
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic<'a>) {
+        if self.options.diagnostics || self.options.strict {
+            self.diagnostics.push(diagnostic);
         }
     }
     pub fn parse(&mut self) -> Vec<Node<'a>> {
@@ -23,94 +196,583 @@ impl<'a> Parser<'a> {
         nodes
     }
 
+    /// Parse the input as a whole document, keeping its doctype.
+    pub fn parse_document(&mut self) -> Document<'a> {
+        Document {
+            nodes: self.parse(),
+        }
+    }
+
+    /// Parse input that may be cut off, such as the part of a streamed
+    /// document received so far, returning only the top-level nodes that
+    /// can't change when more input arrives, and the byte offset where they
+    /// end.
+    ///
+    /// The last node is held back if it could still grow: text, or an
+    /// element whose end tag hasn't been seen (which includes one cut off in
+    /// the middle of a tag). Parsing can resume from the returned offset once
+    /// more input is available.
+    pub fn parse_available(&mut self) -> (Vec<Node<'a>>, usize) {
+        let mut nodes = self.parse();
+        let source = self.tokenizer.source();
+        if nodes.last().is_some_and(|node| !is_complete(source, node)) {
+            nodes.pop();
+        }
+        let end = nodes.last().map_or(0, |node| match &node.kind {
+            NodeKind::Element(element) => element.span.byte_range().end,
+            NodeKind::Text(token) | NodeKind::CData(token) => token.span().byte_range().end,
+            NodeKind::Comment(comment) => {
+                comment.span().byte_range().end + comment_end(source, comment)
+            }
+            NodeKind::ProcessingInstruction(token) => token.span().byte_range().end + 2,
+            NodeKind::Doctype(doctype) => doctype.token.span().byte_range().end,
+        });
+        (nodes, end)
+    }
+
+    /// Parse the input, keeping only the elements for which `keep` returns
+    /// true when given their tag name and attributes. The name is the one
+    /// the element is parsed as: as written, or as renamed by
+    /// [`ParserOptions::tag_rewrite`].
+    ///
+    /// A kept element keeps its whole subtree. Everything else is dropped as
+    /// soon as it has been parsed, so memory use is bounded by the kept
+    /// subtrees rather than the whole input. Kept elements nested in dropped
+    /// ones are returned at the top level, in document order.
+    pub fn parse_filtered(&mut self, keep: impl Fn(&str, &[Attribute]) -> bool) -> Vec<Node<'a>> {
+        let (nodes, _) = self.parse_nodes_with(Some(&keep));
+        nodes
+    }
+
     pub fn parse_nodes(&mut self) -> (Vec<Node<'a>>, Option<&'a str>) {
+        self.parse_nodes_with(None)
+    }
+
+    /// Parse nodes, dropping those outside the elements `keep` accepts if
+    /// given. See [`Parser::parse_filtered`].
+    fn parse_nodes_with(
+        &mut self,
+        keep: Option<ElementFilter>,
+    ) -> (Vec<Node<'a>>, Option<&'a str>) {
+        // elements whose content is being parsed, innermost last; `nodes` is
+        // the content parsed so far of the innermost one
+        let mut open_elements: Vec<OpenElement<'a>> = vec![];
         let mut nodes = Vec::new();
-        let mut end_tag = None;
-        while let Some(token) = self.tokenizer.next() {
-            match token.kind() {
-                TokenKind::TagName { name } => {
-                    self.open_tag_stack.push(name);
-                    let attributes = self.parse_attributes();
-
-                    let (attributes, is_self_closing_tag) = if let Some(last) = attributes.last() {
-                        if last.name_text() == "/" && last.value_text() == "" {
-                            let mut attributes = attributes;
-                            attributes.pop();
-                            (attributes, true)
-                        } else {
-                            (attributes, false)
+        loop {
+            // the content of the innermost open element (or the top level)
+            // ends, with the end tag that ended it if any
+            let end_tag = loop {
+                let Some((start, token)) = self.next_token() else {
+                    self.content_end = self.tokenizer.here();
+                    break None;
+                };
+                // whether what is parsed here goes in the tree
+                let in_kept = open_elements
+                    .last()
+                    .map_or(keep.is_none(), |open| open.kept);
+                match token.kind() {
+                    TokenKind::TagName { name } => {
+                        let name = self.rewrite(name);
+                        if self.closes_inline_parent(name) {
+                            self.content_end = start.clone();
+                            self.pending = Some((start, token));
+                            break None;
                         }
-                    } else {
-                        (attributes, false)
-                    };
-                    let (children, node_end_tag) = if !is_self_closing_tag {
-                        self.parse_nodes()
-                    } else {
-                        (vec![], None)
-                    };
-                    if let Some(end_name) = node_end_tag {
-                        if &end_name != name {
-                            // some parent tag ended instead of self
-                            end_tag = Some(end_name);
+                        let token = token.renamed(name);
+                        let namespace = self.namespace.for_child(name);
+                        let (attributes, attribute_count, is_self_closing_tag) =
+                            self.parse_attributes(namespace);
+                        let open_tag = self.tokenizer.join(&start, &self.tokenizer.here());
+                        if namespace == Namespace::Html
+                            && OBSOLETE_ELEMENTS
+                                .iter()
+                                .any(|o| o.eq_ignore_ascii_case(name))
+                        {
+                            self.report(Diagnostic::warning(
+                                "deprecated-element",
+                                format!(
+                                    "`<{name}>` is obsolete; use CSS or a current element instead"
+                                ),
+                                open_tag.clone(),
+                            ));
+                        }
+                        // `/>` closes any element: HTML tags that are written this way
+                        // can't have content anyway, and in foreign content it is
+                        // the only way to write an empty element.
+                        let is_void = namespace == Namespace::Html && is_void_element(name);
+                        let kept = in_kept
+                            || keep.is_some_and(|keep| keep(name, &attributes[..attribute_count]));
+                        if is_self_closing_tag || is_void {
+                            if !kept {
+                                continue;
+                            }
+                            let inner = open_tag.end_point();
+                            let element = Element {
+                                attributes,
+                                attribute_count,
+                                children: vec![],
+                                self_closing: is_self_closing_tag,
+                                lowercase_name: self.options.normalize_tag_case
+                                    && namespace == Namespace::Html,
+                                tag_name: token,
+                                namespace,
+                                span: self.tokenizer.join(&start, &inner),
+                                inner,
+                                parent: ParentLink::default(),
+                            };
+                            nodes.push(Node {
+                                kind: NodeKind::Element(ElementBox::new(element)),
+                            });
+                            continue;
+                        }
+                        self.open_tag_stack.push(name);
+                        let parent_namespace = self.enter_namespace(namespace);
+                        open_elements.push(OpenElement {
+                            start,
+                            name,
+                            tag_name: token,
+                            attributes,
+                            attribute_count,
+                            namespace,
+                            parent_namespace,
+                            open_tag,
+                            siblings: std::mem::take(&mut nodes),
+                            kept,
+                        });
+                    }
+                    TokenKind::TagEnd { name }
+                        if open_elements
+                            .iter()
+                            .any(|open| open.is_closed_by(self.rewrite(name))) =>
+                    {
+                        self.content_end = start;
+                        break Some(self.rewrite(name));
+                    }
+                    TokenKind::TagEnd { name } if self.options.strict => {
+                        self.report(Diagnostic::error(
+                            "stray-end-tag",
+                            format!("`</{name}>` doesn't close any open element and is ignored"),
+                            self.tokenizer.join(&start, &self.tokenizer.here()),
+                        ));
+                    }
+                    TokenKind::Doctype { .. } => {
+                        // only comments and whitespace may come before the doctype
+                        let at_start = self.open_tag_stack.is_empty()
+                            && !self.seen_doctype
+                            && nodes.iter().all(|node| match &node.kind {
+                                NodeKind::Comment(_) => true,
+                                NodeKind::Text(token) => {
+                                    token.span().source().chars().all(is_html_whitespace)
+                                }
+                                _ => false,
+                            });
+                        if at_start {
+                            self.seen_doctype = true;
+                            if !in_kept {
+                                continue;
+                            }
+                            nodes.push(Node {
+                                kind: NodeKind::Doctype(Doctype { token }),
+                            });
                         } else {
-                            self.open_tag_stack.pop();
+                            self.report(Diagnostic::warning(
+                                "misplaced-doctype",
+                                "a doctype is only allowed at the start of the document; this one is ignored",
+                                token.span().clone(),
+                            ));
                         }
                     }
-                    let element = Element {
-                        attributes,
-                        children,
-                        tag_name: token,
-                    };
-                    let node = Node {
-                        kind: NodeKind::Element(element),
-                    };
-                    nodes.push(node);
-                    if end_tag.is_some() {
-                        break;
+                    TokenKind::ProcessingInstruction { .. } if !in_kept => {}
+                    TokenKind::ProcessingInstruction { .. } => {
+                        let node = Node {
+                            kind: NodeKind::ProcessingInstruction(token),
+                        };
+                        nodes.push(node);
                     }
-                }
-                TokenKind::TagEnd { name } => {
-                    if self.open_tag_stack.contains(name) {
-                        end_tag = Some(*name);
-                        break;
+                    TokenKind::CData { .. } | TokenKind::Comment { .. } if !in_kept => {}
+                    TokenKind::CData { .. } => {
+                        let node = Node {
+                            kind: NodeKind::CData(token),
+                        };
+                        nodes.push(node);
                     }
+                    TokenKind::Comment { .. } => {
+                        let node = Node {
+                            kind: NodeKind::Comment(Comment { token }),
+                        };
+                        nodes.push(node);
+                    }
+                    TokenKind::Text { text } => {
+                        // `<` is just text in the content of a raw text element
+                        let raw_text = open_elements.last().is_some_and(|open| {
+                            open.namespace == Namespace::Html && is_raw_text_element(open.name)
+                        });
+                        if self.options.strict && !raw_text {
+                            for (i, _) in text.match_indices('<') {
+                                self.report(Diagnostic::error(
+                                    "bare-less-than",
+                                    "`<` that doesn't start a tag should be written as `&lt;`",
+                                    token.span().subspan(i..i + 1),
+                                ));
+                            }
+                        }
+                        if !in_kept {
+                            continue;
+                        }
+                        let node = Node {
+                            kind: NodeKind::Text(token),
+                        };
+                        nodes.push(node);
+                    }
+                    _ => {}
                 }
-                TokenKind::Text { text: _ } => {
-                    let node = Node {
-                        kind: NodeKind::Text(token),
-                    };
-                    nodes.push(node);
+            };
+            // close open elements until one is ended by its own end tag, or
+            // the top level is reached
+            loop {
+                let Some(mut open) = open_elements.pop() else {
+                    return (nodes, end_tag);
+                };
+                let siblings = std::mem::take(&mut open.siblings);
+                let children = std::mem::replace(&mut nodes, siblings);
+                let kept = open.kept;
+                let (element, ended_by_own_tag) = if kept {
+                    self.close_element(open, children, end_tag)
+                } else {
+                    // only kept elements found inside, which take its place
+                    nodes.extend(children);
+                    self.close_element(open, vec![], end_tag)
+                };
+                if kept {
+                    nodes.push(Node {
+                        kind: NodeKind::Element(ElementBox::new(element)),
+                    });
+                }
+                if ended_by_own_tag || end_tag.is_none() {
+                    break;
                 }
-                _ => {}
             }
         }
-        (nodes, end_tag)
     }
 
-    fn parse_attributes(&mut self) -> Vec<Attribute<'a>> {
-        let mut attributes = vec![];
+    /// Finish `open` with its parsed `children`, now that its content ended
+    /// at `end_tag` (`None` for the end of input or an implied end), and
+    /// whether that was its own end tag.
+    fn close_element(
+        &mut self,
+        open: OpenElement<'a>,
+        children: Vec<Node<'a>>,
+        end_tag: Option<&'a str>,
+    ) -> (Element<'a>, bool) {
+        self.enter_namespace(open.parent_namespace);
+        self.open_tag_stack.pop();
+        let inner = self
+            .tokenizer
+            .join(&open.open_tag.end_point(), &self.content_end);
+        let ended_by_own_tag = end_tag.is_some_and(|name| open.is_closed_by(name));
+        if let Some(end_name) = end_tag.filter(|_| self.options.strict && !ended_by_own_tag) {
+            self.report(Diagnostic::error(
+                "unclosed-element",
+                format!("`<{}>` is closed implicitly by `</{end_name}>`", open.name),
+                open.open_tag.clone(),
+            ));
+        }
+        let span = if ended_by_own_tag {
+            self.tokenizer.join(&open.start, &self.tokenizer.here())
+        } else {
+            self.tokenizer.join(&open.start, &inner)
+        };
+        let element = Element {
+            attributes: open.attributes,
+            attribute_count: open.attribute_count,
+            children,
+            self_closing: false,
+            lowercase_name: self.options.normalize_tag_case && open.namespace == Namespace::Html,
+            tag_name: open.tag_name,
+            namespace: open.namespace,
+            span,
+            inner,
+            parent: ParentLink::default(),
+        };
+        (element, ended_by_own_tag)
+    }
+
+    /// The next token along with an empty span where it starts.
+    fn next_token(&mut self) -> Option<(Span<'a>, Token<'a>)> {
+        self.pending.take().or_else(|| {
+            let start = self.tokenizer.here();
+            self.tokenizer.next().map(|token| (start, token))
+        })
+    }
+
+    /// Switch to parsing in `namespace`, returning the previous one.
+    fn enter_namespace(&mut self, namespace: Namespace) -> Namespace {
+        self.tokenizer.allow_cdata(namespace != Namespace::Html);
+        std::mem::replace(&mut self.namespace, namespace)
+    }
+
+    /// Whether a `name` start tag should implicitly close the inline
+    /// formatting element currently being parsed.
+    fn closes_inline_parent(&self, name: &str) -> bool {
+        self.options.lenient
+            && self.namespace == Namespace::Html
+            && BLOCK_ELEMENTS.iter().any(|b| b.eq_ignore_ascii_case(name))
+            && self.open_tag_stack.last().is_some_and(|open| {
+                INLINE_FORMATTING_ELEMENTS
+                    .iter()
+                    .any(|i| i.eq_ignore_ascii_case(open))
+            })
+    }
+
+    /// Parse the attributes of a start tag up to its end, returning them
+    /// followed by the duplicates dropped from them, how many are kept, and
+    /// whether the tag ended with `/>`.
+    fn parse_attributes(&mut self, namespace: Namespace) -> (Vec<Attribute<'a>>, usize, bool) {
+        let normalize_case = self.options.normalize_attr_case && namespace == Namespace::Html;
+        let mut attributes: Vec<Attribute<'a>> = vec![];
+        let mut duplicates = vec![];
+        let mut dropped = false;
+        let mut reported_duplicate = false;
+        let mut self_closing = false;
         while let Some(token) = self.tokenizer.next() {
             match token.kind() {
                 TokenKind::AttributeName { name: _ } => {
+                    let value_start = self.tokenizer.here().byte_range().start;
                     let value_token = self
                         .tokenizer
                         .next()
                         .expect("Attribute value should always exist");
+                    // everything the value was read from, `=` and all
+                    let read = &self.tokenizer.source()
+                        [value_start..self.tokenizer.here().byte_range().start];
+                    let raw_value = read
+                        .trim_start_matches(is_html_whitespace)
+                        .strip_prefix('=')
+                        .map_or(&read[read.len()..], |rest| {
+                            rest.trim_start_matches(is_html_whitespace)
+                        });
                     let attribute = Attribute {
+                        lowercase_name: normalize_case,
                         name: token,
                         value: value_token,
+                        raw_value,
                     };
-                    attributes.push(attribute);
+                    if attribute.name_text().is_empty() {
+                        self.report(Diagnostic::warning(
+                            "missing-attribute-name",
+                            "attribute value without a name is ignored",
+                            attribute.value.span().clone(),
+                        ));
+                        continue;
+                    }
+                    // duplicates are kept for `Element::raw_attributes`, so
+                    // they count towards the limit too
+                    match self.options.max_attributes_per_element {
+                        Some(max) if attributes.len() + duplicates.len() >= max => {
+                            if !dropped {
+                                self.report(Diagnostic::warning(
+                                    "too-many-attributes",
+                                    format!(
+                                        "only the first {max} attributes of an element are kept"
+                                    ),
+                                    attribute.name.span().clone(),
+                                ));
+                            }
+                            dropped = true;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    let name = attribute.name_text();
+                    let is_duplicate = attributes.iter().any(|a| {
+                        if namespace == Namespace::Html {
+                            a.name_eq_ignore_ascii_case(name)
+                        } else {
+                            a.name_text() == name
+                        }
+                    });
+                    if is_duplicate && !self.options.keep_duplicate_attributes {
+                        if !reported_duplicate {
+                            self.report(Diagnostic::warning(
+                                "duplicate-attribute",
+                                format!(
+                                    "`{name}` repeats an earlier attribute; only the first attribute with each name is kept"
+                                ),
+                                attribute.name.span().clone(),
+                            ));
+                        }
+                        reported_duplicate = true;
+                        duplicates.push(attribute);
+                    } else {
+                        attributes.push(attribute);
+                    }
                 }
                 TokenKind::OpeningTagEnd => break,
+                TokenKind::SelfClosingTagEnd => {
+                    self_closing = true;
+                    break;
+                }
                 _ => {}
             }
         }
-        attributes
+        let count = attributes.len();
+        attributes.extend(duplicates);
+        (attributes, count, self_closing)
+    }
+}
+
+/// The length of what closes `comment` in `source` after its text: `-->`, the
+/// `>` of a bogus comment, or nothing when cut off.
+fn comment_end(source: &str, comment: &Comment) -> usize {
+    let rest = &source[comment.span().byte_range().end..];
+    if rest.starts_with("-->") {
+        3
+    } else if rest.starts_with('>') {
+        1
+    } else {
+        0
     }
 }
 
+/// Whether `node`, parsed from `source`, is finished, so that more input
+/// after it can't change it. Text never is, as more text would extend it.
+fn is_complete(source: &str, node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::Element(element) => element.is_closed(),
+        NodeKind::Comment(comment) => comment_end(source, comment) > 0,
+        // unless cut off, the instruction is followed by its `?>`
+        NodeKind::ProcessingInstruction(token) => {
+            source[token.span().byte_range().end..].starts_with("?>")
+        }
+        NodeKind::Doctype(doctype) => doctype.token.span().source().ends_with('>'),
+        NodeKind::Text(_) | NodeKind::CData(_) => false,
+    }
+}
+
+/// `s` with ASCII letters lowercased if `lowercase` is set, borrowing it when
+/// nothing changes.
+fn lowercased(s: &str, lowercase: bool) -> Cow<'_, str> {
+    if lowercase && s.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(s.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+const INLINE_FORMATTING_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "big", "cite", "code", "dfn", "em", "font", "i", "kbd", "mark",
+    "q", "s", "samp", "small", "span", "strike", "strong", "sub", "sup", "tt", "u", "var",
+];
+
+/// Elements that never have content or an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+fn is_void_element(name: &str) -> bool {
+    VOID_ELEMENTS.iter().any(|v| v.eq_ignore_ascii_case(name))
+}
+
+/// Elements whose content is text up to their end tag, with no markup in it.
+/// `xmp` and `listing` are obsolete, but still treated this way.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "xmp", "listing"];
+
+/// Elements whose whitespace is significant, which are never reformatted.
+const PREFORMATTED_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style", "xmp", "listing"];
+
+/// Elements whose content is text up to their end tag like that of raw text
+/// elements, but in which character references still count.
+const ESCAPABLE_RAW_TEXT_ELEMENTS: &[&str] = &["textarea", "title"];
+
+/// Whether the content of an HTML `name` element is text up to its end tag.
+fn is_raw_text_element(name: &str) -> bool {
+    RAW_TEXT_ELEMENTS
+        .iter()
+        .chain(ESCAPABLE_RAW_TEXT_ELEMENTS)
+        .any(|r| r.eq_ignore_ascii_case(name))
+}
+
+/// `type`s of a `<script>` that hold code to run rather than a data block.
+const SCRIPT_TYPES: &[&str] = &["", "text/javascript", "module"];
+
+const OBSOLETE_ELEMENTS: &[&str] = &["big", "blink", "center", "font", "marquee", "tt"];
+
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "dialog",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// A parsed document: its top-level nodes, including its doctype if it
+/// starts with one.
 #[derive(Debug)]
+pub struct Document<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Document<'a> {
+    /// The name given by the document's `<!DOCTYPE>`, such as `html`.
+    pub fn doctype(&self) -> Option<&'a str> {
+        self.doctype_node().map(|doctype| doctype.name())
+    }
+
+    /// The document's `<!DOCTYPE>`, if it has one.
+    pub fn doctype_node(&self) -> Option<&Doctype<'a>> {
+        self.nodes.iter().find_map(|node| match &node.kind {
+            NodeKind::Doctype(doctype) => Some(doctype),
+            _ => None,
+        })
+    }
+
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+
+    /// Iterate over the elements among the top-level nodes, usually just
+    /// `<html>`.
+    pub fn root_elements(&self) -> impl Iterator<Item = &Element<'a>> {
+        self.nodes.iter().filter_map(|node| match &node.kind {
+            NodeKind::Element(element) => Some(&**element),
+            _ => None,
+        })
+    }
+}
+
+/// Nodes compare equal when they have the same structure and text,
+/// regardless of where in the source they were parsed from; see
+/// [`Element`]'s `PartialEq` for what elements compare.
+#[derive(Debug, PartialEq)]
 pub struct Node<'a> {
     kind: NodeKind<'a>,
 }
@@ -118,24 +780,672 @@ pub struct Node<'a> {
 #[derive(Debug)]
 pub enum NodeKind<'a> {
     Text(Token<'a>),
-    Element(Element<'a>),
-    Comment(Token<'a>),
+    Element(ElementBox<'a>),
+    Comment(Comment<'a>),
+    /// A `<![CDATA[...]]>` section in foreign content. In HTML the same
+    /// markup is a bogus comment.
+    CData(Token<'a>),
+    /// A `<?target data?>` processing instruction, as found in XML.
+    ProcessingInstruction(Token<'a>),
+    /// The `<!DOCTYPE>` at the start of a document. Doctypes anywhere else
+    /// are dropped.
+    Doctype(Doctype<'a>),
+}
+
+impl PartialEq for NodeKind<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (NodeKind::Text(a), NodeKind::Text(b))
+            | (NodeKind::CData(a), NodeKind::CData(b))
+            | (NodeKind::ProcessingInstruction(a), NodeKind::ProcessingInstruction(b)) => {
+                a.kind() == b.kind()
+            }
+            (NodeKind::Element(a), NodeKind::Element(b)) => a == b,
+            (NodeKind::Comment(a), NodeKind::Comment(b)) => a == b,
+            (NodeKind::Doctype(a), NodeKind::Doctype(b)) => a.token.kind() == b.token.kind(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Element<'a> {
+    /// The attributes kept, followed by those dropped for repeating the name
+    /// of an earlier one.
     attributes: Vec<Attribute<'a>>,
+    /// How many of `attributes` are kept.
+    attribute_count: usize,
     children: Vec<Node<'a>>,
+    /// Whether the start tag ended with `/>`.
+    self_closing: bool,
+    /// Whether [`ParserOptions::normalize_tag_case`] applies to the tag name.
+    lowercase_name: bool,
     tag_name: Token<'a>,
+    namespace: Namespace,
+    /// The whole element, from its start tag up to its end tag if it has one.
+    span: Span<'a>,
+    /// Everything between the start tag and the end tag.
+    inner: Span<'a>,
+    /// The element this one is a child of, unless it is at the top level.
+    parent: ParentLink<'a>,
+}
+
+/// An [`Element`] in a [`Node`], owned like a `Box` would own it. The
+/// element's children point back at it, so it must stay at the same address
+/// however the node is moved, which a `Box` doesn't promise to raw pointers.
+pub struct ElementBox<'a>(NonNull<Element<'a>>);
+
+impl<'a> ElementBox<'a> {
+    /// Move `element` to the heap, linking its child elements to it.
+    fn new(element: Element<'a>) -> Self {
+        let mut boxed = Self(NonNull::from(Box::leak(Box::new(element))));
+        let parent = boxed.0;
+        for child in &mut boxed.children {
+            if let NodeKind::Element(child) = &mut child.kind {
+                child.parent = ParentLink(Some(parent));
+            }
+        }
+        boxed
+    }
+}
+
+impl<'a> Deref for ElementBox<'a> {
+    type Target = Element<'a>;
+
+    fn deref(&self) -> &Element<'a> {
+        // SAFETY: the pointer comes from a `Box` that only `drop` frees
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<'a> DerefMut for ElementBox<'a> {
+    fn deref_mut(&mut self) -> &mut Element<'a> {
+        // SAFETY: as for `deref`, and `&mut self` makes the access unique
+        unsafe { self.0.as_mut() }
+    }
+}
+
+impl Drop for ElementBox<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the pointer comes from `Box::leak` and is freed only here
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+impl PartialEq for ElementBox<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for ElementBox<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// SAFETY: an `ElementBox` owns its element as a `Box` would
+unsafe impl Send for ElementBox<'_> {}
+unsafe impl Sync for ElementBox<'_> {}
+
+/// A pointer from an element to its parent, which owns the element through
+/// its children and so outlives it. Trees can't be changed once parsed, so
+/// the parent stays where it is.
+#[derive(Default)]
+struct ParentLink<'a>(Option<NonNull<Element<'a>>>);
+
+impl fmt::Debug for ParentLink<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the address would make the output differ from run to run
+        f.write_str(if self.0.is_some() { "Some(..)" } else { "None" })
+    }
+}
+
+// SAFETY: the link only gives shared access to the parent, which is `Sync`
+unsafe impl Send for ParentLink<'_> {}
+unsafe impl Sync for ParentLink<'_> {}
+
+impl Drop for Element<'_> {
+    fn drop(&mut self) {
+        // drop the descendants one at a time, as dropping them recursively
+        // would overflow the stack on deeply nested documents
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            if let NodeKind::Element(element) = &mut node.kind {
+                stack.append(&mut element.children);
+            }
+        }
+    }
+}
+
+/// The namespace an element was parsed in.
+///
+/// `<svg>` and `<math>` start foreign content: inside them tag names keep
+/// their case and `/>` closes any element. HTML-only rules (void elements,
+/// case folding) don't apply there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+/// Elements are equal when their [names](Element::name), namespaces,
+/// attributes and children are. Spans are ignored, so the same markup
+/// parsed at different offsets compares equal, as do attributes that only
+/// differ in quoting: `a=x` equals `a="x"`.
+impl PartialEq for Element<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+            && self.namespace == other.namespace
+            && self.attributes() == other.attributes()
+            && self.children == other.children
+    }
+}
+
+impl Namespace {
+    /// The namespace of a `name` element opened while in `self`.
+    fn for_child(self, name: &str) -> Self {
+        match self {
+            Namespace::Html if name.eq_ignore_ascii_case("svg") => Namespace::Svg,
+            Namespace::Html if name.eq_ignore_ascii_case("math") => Namespace::MathMl,
+            namespace => namespace,
+        }
+    }
+}
+
+impl<'a> Element<'a> {
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// The tag name, lowercased if [`ParserOptions::normalize_tag_case`] is
+    /// set and this is an HTML element, otherwise as written or as renamed by
+    /// [`ParserOptions::tag_rewrite`].
+    pub fn name(&self) -> Cow<'a, str> {
+        lowercased(self.parsed_name(), self.lowercase_name)
+    }
+
+    /// The tag name the element was parsed as, which differs from
+    /// [`Element::tag_name`] when [`ParserOptions::tag_rewrite`] renamed it.
+    fn parsed_name(&self) -> &'a str {
+        match self.tag_name.kind() {
+            TokenKind::TagName { name } => name,
+            _ => self.tag_name(),
+        }
+    }
+
+    /// The tag name exactly as written in the source.
+    pub fn tag_name(&self) -> &'a str {
+        self.tag_name.span().source()
+    }
+
+    /// The attributes, without those that repeat the name of an earlier one
+    /// unless [`ParserOptions::keep_duplicate_attributes`] is set.
+    pub fn attributes(&self) -> &[Attribute<'a>] {
+        &self.attributes[..self.attribute_count]
+    }
+
+    /// Whether this element and `other` have the same tag name, attributes
+    /// and children, looser than `==`: tag names are compared ignoring ASCII
+    /// case, attributes in any order, and only the first attribute with each
+    /// name counts. Descendants are compared the same way, and other nodes
+    /// with `==`.
+    pub fn semantic_eq(&self, other: &Element) -> bool {
+        let mut pairs = vec![(self, other)];
+        while let Some((a, b)) = pairs.pop() {
+            if !a.parsed_name().eq_ignore_ascii_case(b.parsed_name())
+                || a.namespace != b.namespace
+                || a.attribute_map() != b.attribute_map()
+                || a.children.len() != b.children.len()
+            {
+                return false;
+            }
+            for (a, b) in a.children.iter().zip(&b.children) {
+                match (&a.kind, &b.kind) {
+                    (NodeKind::Element(a), NodeKind::Element(b)) => pairs.push((a, b)),
+                    (a, b) if a != b => return false,
+                    _ => {}
+                }
+            }
+        }
+        true
+    }
+
+    /// The value of the first attribute with each name, keyed by the name,
+    /// lowercased on HTML elements where names are case-insensitive.
+    fn attribute_map(&self) -> BTreeMap<Cow<'a, str>, &'a str> {
+        let mut map = BTreeMap::new();
+        for attribute in self.attributes() {
+            let name = lowercased(attribute.name_text(), self.namespace == Namespace::Html);
+            map.entry(name).or_insert(attribute.value_text());
+        }
+        map
+    }
+
+    /// All the attributes as written, duplicates included, in source order.
+    pub fn raw_attributes(&self) -> Vec<&Attribute<'a>> {
+        let mut attributes = self.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_by_key(|a| a.name.span().byte_range().start);
+        attributes
+    }
+
+    pub fn children(&self) -> &[Node<'a>] {
+        &self.children
+    }
+
+    /// The span of the whole element, from its start tag up to its end tag.
+    pub fn span(&self) -> &Span<'a> {
+        &self.span
+    }
+
+    /// The source between the start and end tags, exactly as written.
+    ///
+    /// For an element without an end tag this runs to the end of its last
+    /// child; for a self-closing element it is empty.
+    pub fn inner_source(&self) -> &'a str {
+        self.inner.source()
+    }
+
+    /// The whole element exactly as written, from its start tag up to its
+    /// end tag if it has one, whitespace inside tags included. Unlike
+    /// [`Element::to_html`], which normalizes the tags, this round-trips.
+    pub fn outer_source(&self) -> &'a str {
+        self.span.source()
+    }
+
+    /// Whether this element was closed by its end tag, by `/>` or, for a void
+    /// element, by the end of its start tag, rather than by the end of the
+    /// input or of a parent.
+    fn is_closed(&self) -> bool {
+        if self.span.byte_range().end > self.inner.byte_range().end {
+            self.span.source().ends_with('>')
+        } else {
+            self.self_closing
+                || self.namespace == Namespace::Html
+                    && is_void_element(self.parsed_name())
+                    && self.span.source().ends_with('>')
+        }
+    }
+
+    /// Whether the start tag ended with `/>`, as in `<br/>`. Such an element
+    /// has no children.
+    pub fn is_self_closing(&self) -> bool {
+        self.self_closing
+    }
+
+    /// The span of the start tag.
+    pub(crate) fn start_tag(&self) -> Span<'a> {
+        let length = self.inner.byte_range().start - self.span.byte_range().start;
+        self.span.subspan(0..length)
+    }
+
+    /// The start tag exactly as written in the source, with its attributes.
+    pub fn start_tag_source(&self) -> &'a str {
+        self.start_tag().source()
+    }
+
+    /// Whether this element's tag name is `name`, ignoring ASCII case.
+    pub(crate) fn is_named(&self, name: &str) -> bool {
+        self.parsed_name().eq_ignore_ascii_case(name)
+    }
+
+    /// The value of the first attribute named `name`, compared ignoring ASCII
+    /// case as HTML attribute names are. The value is returned as written.
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attributes()
+            .iter()
+            .find(|a| a.name_eq_ignore_ascii_case(name))
+            .map(|a| a.value_text())
+    }
+
+    /// The value of the first attribute named exactly `name`, for XML
+    /// documents where attribute names are case-sensitive.
+    pub fn attr_exact(&self, name: &str) -> Option<&'a str> {
+        self.attributes()
+            .iter()
+            .find(|a| a.name() == name)
+            .map(|a| a.value_text())
+    }
+
+    /// The value of the first attribute named `name`, with surrounding
+    /// quotes removed like [`Attribute::value_text`]. Names are compared
+    /// ignoring ASCII case on HTML elements and exactly in `<svg>` and
+    /// `<math>` content, where they are case-sensitive.
+    pub fn get_attribute(&self, name: &str) -> Option<&'a str> {
+        if self.namespace == Namespace::Html {
+            return self.attr(name);
+        }
+        self.attributes()
+            .iter()
+            .find(|a| a.name_text() == name)
+            .map(|a| a.value_text())
+    }
+
+    /// Whether this is a `<script>` data block, such as JSON or a template,
+    /// rather than code: its `type` is set to something other than
+    /// `text/javascript` or `module`. Like scripts, data blocks are raw text.
+    pub fn is_data_block(&self) -> bool {
+        self.namespace == Namespace::Html
+            && self.is_named("script")
+            && self.attr("type").is_some_and(|t| {
+                !SCRIPT_TYPES
+                    .iter()
+                    .any(|script| script.eq_ignore_ascii_case(t.trim()))
+            })
+    }
+
+    /// The space-separated names in the `class` attribute, in order as
+    /// written; nothing if there is no `class`.
+    pub fn classes(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.attr("class")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+    }
+
+    /// Whether `name` is one of the [classes](Element::classes) of this
+    /// element. Class names are case-sensitive.
+    pub fn has_class(&self, name: &str) -> bool {
+        self.classes().any(|class| class == name)
+    }
+
+    /// The `data-*` attributes keyed by their names without the `data-`
+    /// prefix, as written: `data-user-id="5"` gives `"user-id" => "5"`. When
+    /// a name repeats, the first attribute wins.
+    pub fn dataset(&self) -> HashMap<&'a str, &'a str> {
+        let mut dataset = HashMap::new();
+        for attribute in self.attributes() {
+            let name = attribute.name_text();
+            if let Some(key) = name
+                .get(..5)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("data-"))
+                .and(name.get(5..))
+                .filter(|key| !key.is_empty())
+            {
+                dataset.entry(key).or_insert_with(|| attribute.value_text());
+            }
+        }
+        dataset
+    }
+
+    /// Like [`Element::dataset`], but with names camelCased the way the DOM's
+    /// `dataset` does: a `-` followed by a lowercase ASCII letter becomes that
+    /// letter in uppercase, so `data-user-id` gives `userId`.
+    pub fn dataset_camel_case(&self) -> HashMap<String, &'a str> {
+        let mut dataset = HashMap::new();
+        for (name, value) in self.dataset() {
+            let mut key = String::with_capacity(name.len());
+            let mut chars = name.chars().peekable();
+            while let Some(c) = chars.next() {
+                match chars.peek() {
+                    Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                        key.push(next.to_ascii_uppercase());
+                        chars.next();
+                    }
+                    _ => key.push(c),
+                }
+            }
+            dataset.entry(key).or_insert(value);
+        }
+        dataset
+    }
+
+    /// Iterate over the elements among this element's children.
+    pub fn child_elements(&self) -> impl Iterator<Item = &Element<'a>> {
+        self.children.iter().filter_map(|child| match &child.kind {
+            NodeKind::Element(element) => Some(&**element),
+            _ => None,
+        })
+    }
+
+    /// Iterate over all elements nested inside this one, in document order.
+    ///
+    /// The element itself is not included. Traversal uses an explicit stack, so
+    /// deeply nested trees don't grow the call stack.
+    pub fn descendant_elements(&self) -> DescendantElements<'_, 'a> {
+        DescendantElements {
+            stack: self.children.iter().rev().collect(),
+        }
+    }
+
+    /// Whether any element nested inside this one matches `pred`, stopping at
+    /// the first that does. The element itself isn't considered.
+    pub fn has_descendant(&self, pred: impl Fn(&Element) -> bool) -> bool {
+        self.descendant_elements().any(pred)
+    }
+
+    /// Iterate over the elements nested inside this one named `name`
+    /// (ignoring ASCII case), in document order, with their depth below this
+    /// element: children are at depth 1, grandchildren at 2 and so on.
+    pub fn elements_by_tag_with_depth<'s>(
+        &'s self,
+        name: &'s str,
+    ) -> impl Iterator<Item = (usize, &'s Element<'a>)> {
+        let mut stack = self
+            .children
+            .iter()
+            .rev()
+            .map(|c| (1, c))
+            .collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            while let Some((depth, node)) = stack.pop() {
+                if let NodeKind::Element(element) = &node.kind {
+                    stack.extend(element.children.iter().rev().map(|c| (depth + 1, c)));
+                    return Some((depth, &**element));
+                }
+            }
+            None
+        })
+        .filter(move |(_, element)| element.is_named(name))
+    }
+
+    /// The element this one is a child of, or `None` at the top level.
+    pub fn parent(&self) -> Option<&Element<'a>> {
+        // SAFETY: the parent owns this element through its children, so it
+        // is alive for as long as this element is borrowed, and parsed trees
+        // are never changed, so nothing has moved it or borrows it mutably
+        self.parent.0.map(|parent| unsafe { parent.as_ref() })
+    }
+
+    /// Iterate over the elements this one is nested in, from its parent up
+    /// to the top level.
+    pub fn ancestors(&self) -> impl Iterator<Item = &Element<'a>> {
+        std::iter::successors(self.parent(), |element| element.parent())
+    }
+
+    /// This element followed by its ancestors.
+    fn self_and_ancestors(&self) -> impl Iterator<Item = &Element<'a>> {
+        std::iter::once(self).chain(self.ancestors())
+    }
+
+    /// The nearest element carrying the attribute `attr`, starting from this
+    /// one and walking up its ancestors.
+    pub fn closest_with_attr(&self, attr: &str) -> Option<&Element<'a>> {
+        self.self_and_ancestors()
+            .find(|element| element.attr(attr).is_some())
+    }
+
+    /// The language of this element: its own `lang` or that of its nearest
+    /// ancestor with one. An empty `lang` marks the language as unknown,
+    /// giving `None`.
+    pub fn effective_lang(&self) -> Option<&'a str> {
+        self.closest_with_attr("lang")?
+            .attr("lang")
+            .filter(|lang| !lang.is_empty())
+    }
+
+    /// The text direction of this element: its own `dir` or that of its
+    /// nearest ancestor with one, like [`Element::effective_lang`].
+    ///
+    /// Only `ltr`, `rtl` and `auto` (in any case) count; an element with
+    /// another value inherits its direction as if it had no `dir`.
+    pub fn effective_dir(&self) -> Option<&'a str> {
+        self.self_and_ancestors().find_map(|element| {
+            element.attr("dir").filter(|dir| {
+                ["ltr", "rtl", "auto"]
+                    .iter()
+                    .any(|d| dir.eq_ignore_ascii_case(d))
+            })
+        })
+    }
+
+    /// The position of `target` in document order among the elements nested
+    /// in this one that share its tag name, counting from 0 — the index an
+    /// `:nth-of-type`-style selector would need.
+    ///
+    /// Elements are identified by their span, so `target` may come from a
+    /// separate parse of the same source.
+    pub fn ordinal_of(&self, target: &Element) -> Option<usize> {
+        let name = target.tag_name.span().source();
+        self.descendant_elements()
+            .filter(|element| element.is_named(name))
+            .position(|element| element.span.byte_range() == target.span.byte_range())
+    }
+
+    /// Iterate over every attribute in this subtree (this element included),
+    /// paired with the element that owns it.
+    pub fn all_attributes(&self) -> impl Iterator<Item = (&Element<'a>, &Attribute<'a>)> {
+        std::iter::once(self)
+            .chain(self.descendant_elements())
+            .flat_map(|element| element.attributes().iter().map(move |a| (element, a)))
+    }
+
+    /// All the text nested in this element concatenated in document order,
+    /// without any tags, for getting at the visible text of a page.
+    ///
+    /// The content of `<script>` and `<style>` elements isn't visible, so it
+    /// is left out; CDATA sections are included. Character references like
+    /// `&amp;` are kept as written.
+    pub fn text_content(&self) -> String {
+        self.visible_text(false)
+    }
+
+    /// The number of words in the [text content](Element::text_content) of
+    /// this element, separated by ASCII whitespace or a `<br>`. Words are
+    /// whatever lies between those, so `a<b>c</b>` is one word.
+    pub fn word_count(&self) -> usize {
+        self.visible_text(true).split_ascii_whitespace().count()
+    }
+
+    /// The text nested in this element without `<script>` and `<style>`
+    /// content, with a newline for each `<br>` if `line_breaks` is set.
+    fn visible_text(&self, line_breaks: bool) -> String {
+        let mut text = String::new();
+        let mut stack = self.children.iter().rev().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            match &node.kind {
+                NodeKind::Text(token) | NodeKind::CData(token) => {
+                    text.push_str(token.span().source());
+                }
+                NodeKind::Element(element) if line_breaks && element.is_named("br") => {
+                    text.push('\n');
+                }
+                NodeKind::Element(element)
+                    if element.namespace == Namespace::Html
+                        && (element.is_named("script") || element.is_named("style")) => {}
+                NodeKind::Element(element) => stack.extend(element.children.iter().rev()),
+                NodeKind::Comment(_)
+                | NodeKind::ProcessingInstruction(_)
+                | NodeKind::Doctype(_) => {}
+            }
+        }
+        text
+    }
+
+    /// The span of every occurrence of `needle` in the text nested in this
+    /// element, in document order, for highlighting matches in the source.
+    ///
+    /// Each text node is searched on its own, so a match can't run across
+    /// tags. An empty `needle` matches nothing.
+    pub fn find_text(&self, needle: &str) -> Vec<Span<'a>> {
+        if needle.is_empty() {
+            return vec![];
+        }
+        let mut found = vec![];
+        let mut stack = self.children.iter().rev().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            match &node.kind {
+                NodeKind::Text(token) | NodeKind::CData(token) => {
+                    let span = token.span();
+                    found.extend(
+                        span.source()
+                            .match_indices(needle)
+                            .map(|(i, _)| span.subspan(i..i + needle.len())),
+                    );
+                }
+                NodeKind::Element(element) => stack.extend(element.children.iter().rev()),
+                NodeKind::Comment(_)
+                | NodeKind::ProcessingInstruction(_)
+                | NodeKind::Doctype(_) => {}
+            }
+        }
+        found
+    }
+
+    /// Iterate over the text of every comment nested in this element, in
+    /// document order, without the `<!--` and `-->` around it.
+    pub fn comments(&self) -> impl Iterator<Item = &'a str> + '_ {
+        let mut stack = self.children.iter().rev().collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            while let Some(node) = stack.pop() {
+                match &node.kind {
+                    NodeKind::Comment(comment) => return Some(comment.text()),
+                    NodeKind::Element(element) => stack.extend(element.children.iter().rev()),
+                    NodeKind::Text(_)
+                    | NodeKind::CData(_)
+                    | NodeKind::ProcessingInstruction(_)
+                    | NodeKind::Doctype(_) => {}
+                }
+            }
+            None
+        })
+    }
+}
+
+pub struct DescendantElements<'n, 'a> {
+    stack: Vec<&'n Node<'a>>,
+}
+
+/// Iterate over every element in `nodes` and their descendants, in document order.
+pub(crate) fn elements<'n, 'a>(nodes: &'n [Node<'a>]) -> DescendantElements<'n, 'a> {
+    DescendantElements {
+        stack: nodes.iter().rev().collect(),
+    }
+}
+
+impl<'n, 'a> Iterator for DescendantElements<'n, 'a> {
+    type Item = &'n Element<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if let NodeKind::Element(element) = &node.kind {
+                self.stack.extend(element.children.iter().rev());
+                return Some(element);
+            }
+        }
+        None
+    }
 }
 
 #[derive(Debug)]
 pub struct Attribute<'a> {
+    /// Whether [`ParserOptions::normalize_attr_case`] applies to the name.
+    lowercase_name: bool,
     name: Token<'a>,
     value: Token<'a>,
+    /// The value as written, with its quotes.
+    raw_value: &'a str,
 }
 
 impl<'a> Attribute<'a> {
+    /// The value exactly as written in the source, including its quotes, so
+    /// that a serializer can reproduce the original quoting. Unlike
+    /// [`Attribute::value_text`], `title='x'` gives `'x'`. An unquoted value
+    /// is the same either way, and an attribute without a value gives `""`.
+    pub fn raw_value(&self) -> &'a str {
+        self.raw_value
+    }
+
     pub fn value_text(&self) -> &'a str {
         let span = self.value.span();
         let source = span.source();
@@ -145,50 +1455,170 @@ impl<'a> Attribute<'a> {
             source
         }
     }
-    pub fn name_text(&self) -> &'a str {
-        let span = self.name.span();
-        
-        (span.source()) as _
+
+    /// The value with `%XX` percent-encoded bytes decoded, as wanted for URLs
+    /// in `href` and `src` values. Malformed sequences like `%zz` are kept
+    /// as written, and decoded bytes that aren't valid UTF-8 become U+FFFD.
+    pub fn value_percent_decoded(&self) -> Cow<'a, str> {
+        let value = self.value_text();
+        if !value.contains('%') {
+            return Cow::Borrowed(value);
+        }
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            // `from_str_radix` alone would accept a sign, as in `%+5`
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[i], hex) {
+                (b'%', Some(byte)) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+    }
+
+    /// The name exactly as written in the source.
+    pub fn name_text(&self) -> &'a str {
+        let span = self.name.span();
+
+        (span.source()) as _
+    }
+
+    /// The name, lowercased if [`ParserOptions::normalize_attr_case`] is set
+    /// and the attribute is on an HTML element, otherwise as written.
+    pub fn name(&self) -> Cow<'a, str> {
+        lowercased(self.name_text(), self.lowercase_name)
+    }
+
+    /// Whether this attribute is named `other`, ignoring ASCII case as HTML
+    /// attribute names are. Values are case-sensitive and compared as they
+    /// are; [`Attribute::name_text`] keeps the name as written.
+    pub fn name_eq_ignore_ascii_case(&self, other: &str) -> bool {
+        self.name_text().eq_ignore_ascii_case(other)
+    }
+}
+
+#[derive(Debug)]
+pub struct Comment<'a> {
+    token: Token<'a>,
+}
+
+/// Attributes are equal when their [names](Attribute::name) and
+/// [values](Attribute::value_text) are, wherever they are in the source.
+impl PartialEq for Attribute<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name() && self.value_text() == other.value_text()
+    }
+}
+
+/// Comments are equal when their text is.
+impl PartialEq for Comment<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.text() == other.text()
+    }
+}
+
+impl<'a> Comment<'a> {
+    /// The text between `<!--` and `-->`, exactly as written.
+    pub fn text(&self) -> &'a str {
+        self.token.span().source()
+    }
+
+    /// The span of the comment's text.
+    pub fn span(&self) -> &Span<'a> {
+        self.token.span()
+    }
+}
+
+/// A `<!DOCTYPE>` declaration.
+#[derive(Debug)]
+pub struct Doctype<'a> {
+    token: Token<'a>,
+}
+
+impl<'a> Doctype<'a> {
+    /// The root element name, such as `html`, as written.
+    pub fn name(&self) -> &'a str {
+        match self.token.kind() {
+            TokenKind::Doctype { name, .. } => name,
+            _ => "",
+        }
+    }
+
+    /// The public identifier of a legacy doctype like
+    /// `<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">`.
+    pub fn public_id(&self) -> Option<&'a str> {
+        match self.token.kind() {
+            TokenKind::Doctype { public_id, .. } => *public_id,
+            _ => None,
+        }
+    }
+
+    /// The system identifier of a legacy doctype, usually the URL of its DTD.
+    pub fn system_id(&self) -> Option<&'a str> {
+        match self.token.kind() {
+            TokenKind::Doctype { system_id, .. } => *system_id,
+            _ => None,
+        }
+    }
+
+    /// The span of the whole declaration.
+    pub fn span(&self) -> &Span<'a> {
+        self.token.span()
     }
 }
 
-impl<'a> Display for Node<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a> Node<'a> {
+    pub fn kind(&self) -> &NodeKind<'a> {
+        &self.kind
+    }
+
+    /// Where this node is in the source: a whole element or doctype, the text
+    /// of a text node, or the content of a comment, CDATA section or
+    /// processing instruction, without the markup around it.
+    pub fn span(&self) -> &Span<'a> {
         match &self.kind {
-            NodeKind::Element(Element {
-                attributes,
-                children,
-                tag_name,
-            }) => {
-                write!(
-                    f,
-                    "({}{} {})",
-                    tag_name.span().source(),
-                    attributes
-                        .iter()
-                        .map(|a| a.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" "),
-                    children
-                        .iter()
-                        .map(|a| a.to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                )
-            }
-            NodeKind::Text(token) => {
-                write!(f, "#text({})", token.span().source())
-            }
-            NodeKind::Comment(token) => {
-                write!(f, "/*{}*/", token.span().source())
-            }
+            NodeKind::Element(element) => element.span(),
+            NodeKind::Text(token)
+            | NodeKind::CData(token)
+            | NodeKind::ProcessingInstruction(token) => token.span(),
+            NodeKind::Comment(comment) => comment.span(),
+            NodeKind::Doctype(doctype) => doctype.span(),
         }
     }
-}
 
-impl<'a> Display for Attribute<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}=\"{}\"]", self.name_text(), self.value_text())
+    /// The 0-based line and column where [`Node::span`] starts. See
+    /// [`Span::start`].
+    pub fn position(&self) -> (usize, usize) {
+        self.span().start()
+    }
+
+    /// Iterate over this node and every node nested in it, depth-first in
+    /// document order. The node itself comes first; use
+    /// [`Element::descendant_elements`] to get only the elements below one.
+    ///
+    /// Traversal uses an explicit stack, so deeply nested trees don't grow the
+    /// call stack.
+    pub fn descendants(&self) -> impl Iterator<Item = &Node<'a>> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            if let NodeKind::Element(element) = &node.kind {
+                stack.extend(element.children.iter().rev());
+            }
+            Some(node)
+        })
     }
 }
 
@@ -197,6 +1627,34 @@ mod tests {
     use super::*;
     use tokenizer::TokenKind;
 
+    /// `node` as an s-expression, like `(p[class="x"] #text(a) (b ))`, to
+    /// compare trees in tests.
+    pub(crate) fn sexp(node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Element(element) => format!(
+                "({}{} {})",
+                element.name(),
+                element
+                    .attributes()
+                    .iter()
+                    .map(|a| format!("[{}=\"{}\"]", a.name(), a.value_text()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                element
+                    .children
+                    .iter()
+                    .map(sexp)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            NodeKind::Text(token) => format!("#text({})", token.span().source()),
+            NodeKind::Comment(comment) => format!("/*{}*/", comment.text()),
+            NodeKind::CData(token) => format!("#cdata({})", token.span().source()),
+            NodeKind::ProcessingInstruction(token) => format!("#pi({})", token.span().source()),
+            NodeKind::Doctype(doctype) => format!("#doctype({})", doctype.name()),
+        }
+    }
+
     #[test]
     fn test_basic_html_parsing() {
         let html = "<html></html>";
@@ -272,6 +1730,46 @@ mod tests {
         assert_eq!(nodes.len(), 0);
     }
 
+    #[test]
+    fn test_nested_identical_tags() {
+        // an end tag closes the nearest open element with its name
+        let html = "<div id=a><div id=b>x</div>y</div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(div[id=\"a\"] (div[id=\"b\"] #text(x)) #text(y))"
+        );
+        let NodeKind::Element(outer) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let NodeKind::Element(inner) = &outer.children[0].kind else {
+            panic!("Expected div, got: {:?}", &outer.children[0].kind);
+        };
+        assert!(outer.is_closed() && inner.is_closed());
+        assert_eq!(inner.span.source(), "<div id=b>x</div>");
+        assert_eq!(outer.span.source(), html);
+
+        let nodes = Parser::new("<div><div></div></div>").parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(sexp(&nodes[0]), "(div (div ))");
+
+        // the one end tag closes the inner div, leaving the outer one open
+        let html = "<div><div></div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(sexp(&nodes[0]), "(div (div ))");
+        let NodeKind::Element(outer) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let NodeKind::Element(inner) = &outer.children[0].kind else {
+            panic!("Expected div, got: {:?}", &outer.children[0].kind);
+        };
+        assert!(!outer.is_closed());
+        assert!(inner.is_closed());
+        assert_eq!(inner.span.source(), "<div></div>");
+    }
+
     #[test]
     fn test_unclosed_tags() {
         let html = "<html>";
@@ -279,11 +1777,13 @@ mod tests {
         let nodes = parser.parse();
         assert_eq!(nodes.len(), 1);
         match &nodes[0].kind {
-            NodeKind::Element(Element {
-                attributes,
-                children,
-                tag_name,
-            }) => {
+            NodeKind::Element(element) => {
+                let Element {
+                    attributes,
+                    children,
+                    tag_name,
+                    ..
+                } = &**element;
                 assert_eq!(tag_name.span().source(), "html");
                 assert_eq!(children.len(), 0);
                 assert_eq!(attributes.len(), 0);
@@ -300,21 +1800,25 @@ mod tests {
         // This might not be the correct way to handle this, but it should not panic
         assert_eq!(nodes.len(), 1);
         match &nodes[0].kind {
-            NodeKind::Element(Element {
-                attributes,
-                children,
-                tag_name,
-            }) => {
+            NodeKind::Element(element) => {
+                let Element {
+                    attributes,
+                    children,
+                    tag_name,
+                    ..
+                } = &**element;
                 assert_eq!(tag_name.span().source(), "html");
                 assert_eq!(attributes.len(), 0);
                 let nodes = children;
                 assert_eq!(nodes.len(), 1);
                 match &nodes[0].kind {
-                    NodeKind::Element(Element {
-                        attributes,
-                        children,
-                        tag_name,
-                    }) => {
+                    NodeKind::Element(element) => {
+                        let Element {
+                            attributes,
+                            children,
+                            tag_name,
+                            ..
+                        } = &**element;
                         assert_eq!(tag_name.span().source(), "div");
                         assert_eq!(attributes.len(), 0);
                         assert_eq!(children.len(), 0);
@@ -356,8 +1860,47 @@ mod tests {
         let html = "<!-- comment -->";
         let mut parser = Parser::new(html);
         let nodes = parser.parse();
-        //  For now, comments are ignored
-        assert_eq!(nodes.len(), 0);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(sexp(&nodes[0]), "/* comment */");
+    }
+
+    #[test]
+    fn test_element_comments() {
+        let html = "<body><!-- build:js app.js --><div>a<!--x--><p><!----></p></div><!-- endbuild --></body><!-- outside -->";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 2);
+        let NodeKind::Element(body) = &nodes[0].kind else {
+            panic!("Expected body, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            body.comments().collect::<Vec<_>>(),
+            vec![" build:js app.js ", "x", "", " endbuild "]
+        );
+    }
+
+    #[test]
+    fn test_raw_attribute_values() {
+        let html = "<p title='x' id = \"a b\" class=c data-e=\"\" hidden lang=>t</p>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(element) = &nodes[0].kind else {
+            panic!("Expected p, got: {:?}", &nodes[0].kind);
+        };
+        let values = element
+            .attributes()
+            .iter()
+            .map(|a| (a.name_text(), a.raw_value(), a.value_text()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ("title", "'x'", "x"),
+                ("id", "\"a b\"", "a b"),
+                ("class", "c", "c"),
+                ("data-e", "\"\"", ""),
+                ("hidden", "", ""),
+                ("lang", "", ""),
+            ]
+        );
     }
 
     #[test]
@@ -406,24 +1949,126 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 100_000;
+        let html = "<div>".repeat(depth) + &"</div>".repeat(depth);
+        let nodes = Parser::new(&html).parse();
+        assert_eq!(nodes.len(), 1);
+        let elements = nodes[0].descendants().collect::<Vec<_>>();
+        assert_eq!(elements.len(), depth);
+        let NodeKind::Element(innermost) = &elements[depth - 1].kind else {
+            panic!("Expected div, got: {:?}", &elements[depth - 1].kind);
+        };
+        assert!(innermost.children.is_empty());
+        assert!(innermost.is_closed());
+    }
+
     #[test]
     fn test_doctype() {
         let html = "<!DOCTYPE html><html></html>";
         let mut parser = Parser::new(html);
-        let nodes = parser.parse();
+        let document = parser.parse_document();
 
-        assert_eq!(nodes.len(), 1);
-        match &nodes[0].kind {
+        assert_eq!(document.doctype(), Some("html"));
+        let nodes = document.nodes();
+        assert_eq!(nodes.len(), 2);
+        assert!(matches!(&nodes[0].kind, NodeKind::Doctype(_)));
+        match &nodes[1].kind {
             NodeKind::Element(element) => {
                 assert_eq!(
                     element.tag_name.kind(),
-                    &TokenKind::TagName { name: "!DOCTYPE" }
+                    &TokenKind::TagName { name: "html" }
                 );
             }
             _ => panic!("Expected an element node"),
         }
     }
 
+    #[test]
+    fn test_document_root_elements() {
+        let html =
+            "<!-- a -->\n<!DOCTYPE html>\n<html><body></body></html>\n<!-- b --><p>stray</p>";
+        let document = Parser::new(html).parse_document();
+        assert_eq!(document.doctype(), Some("html"));
+        assert_eq!(
+            document
+                .root_elements()
+                .map(|e| e.tag_name())
+                .collect::<Vec<_>>(),
+            vec!["html", "p"]
+        );
+        assert!(document.nodes().len() > 2);
+    }
+
+    #[test]
+    fn test_doctype_node() {
+        let html = "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">\n<html></html>";
+        let document = Parser::new(html).parse_document();
+        let NodeKind::Doctype(doctype) = &document.nodes()[0].kind else {
+            panic!("Expected a doctype, got: {:?}", &document.nodes()[0].kind);
+        };
+        assert_eq!(doctype.name(), "html");
+        assert_eq!(
+            doctype.public_id(),
+            Some("-//W3C//DTD XHTML 1.0 Strict//EN")
+        );
+        assert_eq!(
+            doctype.system_id(),
+            Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd")
+        );
+        assert_eq!(doctype.span().byte_range(), 0..html.find('\n').unwrap());
+        assert_eq!(
+            document.doctype_node().map(|d| d.name()),
+            document.doctype()
+        );
+        let nodes = Parser::new("<!doctype html><p>x</p>").parse();
+        assert_eq!(sexp(&nodes[0]), "#doctype(html)");
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_misplaced_doctype() {
+        let html =
+            "<!-- generated --><!doctype html><p>text</p><!DOCTYPE html><div><!DOCTYPE x></div>";
+        let options = ParserOptions {
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let document = parser.parse_document();
+        assert_eq!(document.doctype(), Some("html"));
+        assert_eq!(
+            document
+                .nodes()
+                .iter()
+                .map(|node| sexp(node))
+                .collect::<Vec<_>>(),
+            vec![
+                "/* generated */",
+                "#doctype(html)",
+                "(p #text(text))",
+                "(div )"
+            ]
+        );
+        let diagnostics = parser
+            .diagnostics()
+            .iter()
+            .map(|d| (d.code(), d.span().source()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                ("misplaced-doctype", "<!DOCTYPE html>"),
+                ("misplaced-doctype", "<!DOCTYPE x>")
+            ]
+        );
+
+        let document = Parser::new("<p>late</p><!DOCTYPE html>").parse_document();
+        assert_eq!(document.doctype(), None);
+        assert_eq!(document.nodes().len(), 1);
+    }
+
     #[test]
     fn test_whitespace() {
         let html = "  <html  lang =  \"en\"  >  Hello  </html>  ";
@@ -442,6 +2087,7 @@ mod tests {
                     element.attributes[0].name.kind(),
                     &TokenKind::AttributeName { name: "lang" }
                 );
+                assert_eq!(element.attributes[0].value_text(), "en");
             }
             _ => panic!("Expected an element node"),
         }
@@ -643,12 +2289,30 @@ mod tests {
                                 text: "你好，世界！"
                             }
                         );
+                        // each character is 3 bytes but 1 column
+                        let span = text_token.span();
+                        assert_eq!(span.byte_range(), 3..21);
+                        assert_eq!(&html[span.byte_range()], span.source());
+                        assert_eq!((span.start(), span.end()), ((0, 3), (0, 9)));
                     }
                     _ => panic!("Expected a text node"),
                 }
+                assert_eq!(element.span.byte_range(), 0..html.len());
             }
             _ => panic!("Expected an element node"),
         }
+
+        let html = "<p title=\"é\">ü<b>ß</b></p>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(p) = &nodes[0].kind else {
+            panic!("Expected p, got: {:?}", &nodes[0].kind);
+        };
+        let value = p.attributes()[0].value.span();
+        assert_eq!(&html[value.byte_range()], "é");
+        let b = &p.children()[1];
+        assert_eq!(b.span().byte_range(), 16..25);
+        assert_eq!(&html[b.span().byte_range()], "<b>ß</b>");
+        assert_eq!(b.position(), (0, 14));
     }
 
     #[test]
@@ -658,11 +2322,13 @@ mod tests {
         let nodes = parser.parse();
         assert_eq!(nodes.len(), 1);
         match &nodes[0].kind {
-            NodeKind::Element(Element {
-                attributes,
-                children: _,
-                tag_name,
-            }) => {
+            NodeKind::Element(element) => {
+                let Element {
+                    attributes,
+                    children: _,
+                    tag_name,
+                    ..
+                } = &**element;
                 assert_eq!(tag_name.span().source(), "a");
                 assert_eq!(attributes.len(), 1);
                 assert_eq!(attributes[0].name_text(), "href");
@@ -718,9 +2384,9 @@ mod tests {
                     #text( ))
                 #text( ))
             #text( ))";
-        let got = Parser::new(&html).parse();
+        let got = Parser::new(html).parse();
         assert_eq!(got.len(), 1);
-        let got = got[0].to_string();
+        let got = sexp(&got[0]);
         let got = got.split_whitespace().collect::<Vec<_>>().join(" ");
         let expected = expected.split_whitespace().collect::<Vec<_>>().join(" ");
         assert_eq!(got, expected);
@@ -765,4 +2431,1193 @@ mod tests {
             _ => panic!("Expected div, got: {:?}", &nodes[0].kind),
         }
     }
+
+    #[test]
+    fn test_all_attributes() {
+        let html = "<html><body><a href=\"/one\">One</a><div class=\"x\"><p><a href=\"/two\" title=\"t\">Two</a></p></div><a href='/three'/></body></html>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(root) = &nodes[0].kind else {
+            panic!("Expected an element node");
+        };
+        let hrefs = root
+            .all_attributes()
+            .filter(|(_, a)| a.name_text() == "href")
+            .map(|(e, a)| (e.tag_name.span().source(), a.value_text()))
+            .collect::<Vec<_>>();
+        assert_eq!(hrefs, vec![("a", "/one"), ("a", "/two"), ("a", "/three")]);
+        assert_eq!(root.all_attributes().count(), 5);
+    }
+
+    #[test]
+    fn test_svg_self_closing_children() {
+        let html = "<svg><rect/><circle/></svg><p>after</p>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 2);
+        let NodeKind::Element(svg) = &nodes[0].kind else {
+            panic!("Expected svg, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(svg.namespace(), Namespace::Svg);
+        assert_eq!(svg.children.len(), 2);
+        for (child, name) in svg.children.iter().zip(["rect", "circle"]) {
+            let NodeKind::Element(child) = &child.kind else {
+                panic!("Expected {name}, got: {:?}", &child.kind);
+            };
+            assert_eq!(child.tag_name.span().source(), name);
+            assert_eq!(child.namespace(), Namespace::Svg);
+            assert!(child.attributes.is_empty());
+            assert!(child.children.is_empty());
+        }
+        let NodeKind::Element(p) = &nodes[1].kind else {
+            panic!("Expected p, got: {:?}", &nodes[1].kind);
+        };
+        assert_eq!(p.namespace(), Namespace::Html);
+    }
+
+    #[test]
+    fn test_foreign_content_preserves_case() {
+        let html = "<math><mi>x</mi></math><svg viewBox=\"0 0 1 1\"><linearGradient/><clipPath></clipPath></svg>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 2);
+        let NodeKind::Element(math) = &nodes[0].kind else {
+            panic!("Expected math, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(math.namespace(), Namespace::MathMl);
+        let names = match &nodes[1].kind {
+            NodeKind::Element(svg) => svg
+                .descendant_elements()
+                .map(|e| (e.tag_name.span().source(), e.namespace()))
+                .collect::<Vec<_>>(),
+            _ => panic!("Expected svg, got: {:?}", &nodes[1].kind),
+        };
+        assert_eq!(
+            names,
+            vec![
+                ("linearGradient", Namespace::Svg),
+                ("clipPath", Namespace::Svg)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_closed_by_parent_end_tag() {
+        let html = "<p><b>text</p><p>next</p>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            nodes.iter().map(|n| sexp(n)).collect::<Vec<_>>(),
+            vec!["(p (b #text(text)))", "(p #text(next))"]
+        );
+    }
+
+    #[test]
+    fn test_lenient_closes_inline_at_block_start() {
+        let html = "<div><b>bold<p>para</p>after</b></div>";
+        let nodes = Parser::with_options(
+            html,
+            ParserOptions {
+                lenient: true,
+                ..Default::default()
+            },
+        )
+        .parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(div (b #text(bold)) (p #text(para)) #text(after))"
+        );
+
+        // without lenient mode the block stays nested in the inline element
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(div (b #text(bold) (p #text(para)) #text(after)))"
+        );
+    }
+
+    #[test]
+    fn test_inner_source() {
+        let html = "<div>\n  <p class=x >Hello &amp;  <b>world</b></p>\n</div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            div.inner_source(),
+            "\n  <p class=x >Hello &amp;  <b>world</b></p>\n"
+        );
+        let inner = div
+            .descendant_elements()
+            .map(|e| e.inner_source())
+            .collect::<Vec<_>>();
+        assert_eq!(inner, vec!["Hello &amp;  <b>world</b>", "world"]);
+    }
+
+    #[test]
+    fn test_inner_source_unclosed_and_self_closing() {
+        let html = "  <ul><li>one<li>two</ul><br/>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(ul) = &nodes[0].kind else {
+            panic!("Expected ul, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(ul.inner_source(), "<li>one<li>two");
+        assert_eq!(ul.span.source(), "<ul><li>one<li>two</ul>");
+        assert_eq!(ul.span.byte_range(), 2..25);
+        let items = ul
+            .descendant_elements()
+            .map(|e| e.inner_source())
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec!["one<li>two", "two"]);
+        let NodeKind::Element(br) = &nodes[1].kind else {
+            panic!("Expected br, got: {:?}", &nodes[1].kind);
+        };
+        assert_eq!(br.inner_source(), "");
+        assert_eq!(br.span.source(), "<br/>");
+    }
+
+    #[test]
+    fn test_template_expression_attributes() {
+        let html = "<input value={count} /><p>{{ message }}</p>";
+        let options = ParserOptions {
+            template_expressions: true,
+            ..Default::default()
+        };
+        let nodes = Parser::with_options(html, options).parse();
+        assert_eq!(
+            nodes.iter().map(|n| sexp(n)).collect::<Vec<_>>(),
+            vec!["(input[value=\"{count}\"] )", "(p #text({{ message }}))"]
+        );
+    }
+
+    #[test]
+    fn test_closest_with_attr() {
+        let html = "<main data-component=\"page\"><section data-component=\"card\"><div><p><b>deep</b></p></div></section><aside><i>x</i></aside></main>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(main) = &nodes[0].kind else {
+            panic!("Expected main, got: {:?}", &nodes[0].kind);
+        };
+        let find = |name: &str| {
+            main.descendant_elements()
+                .find(|e| e.tag_name.span().source() == name)
+                .expect("element should exist")
+        };
+        let b = find("b");
+        let closest = b
+            .closest_with_attr("data-component")
+            .expect("ancestor should exist");
+        assert_eq!(closest.attr("data-component"), Some("card"));
+        assert_eq!(
+            b.ancestors()
+                .map(|e| e.tag_name.span().source())
+                .collect::<Vec<_>>(),
+            vec!["p", "div", "section", "main"]
+        );
+
+        let i = find("i");
+        let closest = i.closest_with_attr("data-component");
+        assert!(closest.is_some_and(|e| std::ptr::eq(e, &**main)));
+
+        let section = find("section");
+        let closest = section.closest_with_attr("data-component");
+        assert!(closest.is_some_and(|e| std::ptr::eq(e, section)));
+        assert!(b.closest_with_attr("missing").is_none());
+        assert!(main.parent().is_none());
+    }
+
+    #[test]
+    fn test_parent_links() {
+        let html = "<ul><li><a>x</a></li></ul><p><b>y</b></p>";
+        // the links survive the top-level nodes being moved around
+        let mut nodes = Parser::new(html).parse();
+        let p = nodes.remove(1);
+        let NodeKind::Element(p) = &p.kind else {
+            panic!("Expected p, got: {:?}", &p.kind);
+        };
+        let b = p.child_elements().next().expect("p has a child");
+        assert!(b.parent().is_some_and(|parent| std::ptr::eq(parent, &**p)));
+        let NodeKind::Element(ul) = &nodes[0].kind else {
+            panic!("Expected ul, got: {:?}", &nodes[0].kind);
+        };
+        let a = ul.descendant_elements().last().expect("ul has descendants");
+        assert_eq!(
+            a.ancestors().map(|e| e.name()).collect::<Vec<_>>(),
+            vec!["li", "ul"]
+        );
+
+        // elements kept from inside dropped ones end up at the top level
+        let nodes = Parser::new(html).parse_filtered(|name, _| name != "ul" && name != "li");
+        let NodeKind::Element(a) = &nodes[0].kind else {
+            panic!("Expected a, got: {:?}", &nodes[0].kind);
+        };
+        assert!(a.parent().is_none());
+    }
+
+    #[test]
+    fn test_ordinal_of() {
+        let html = "<body><p>a</p><div><p>b</p><span>x</span></div><p>c</p><p>d</p></body>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(body) = &nodes[0].kind else {
+            panic!("Expected body, got: {:?}", &nodes[0].kind);
+        };
+        let paragraphs = body
+            .descendant_elements()
+            .filter(|e| e.is_named("p"))
+            .collect::<Vec<_>>();
+        assert_eq!(paragraphs[2].inner_source(), "c");
+        assert_eq!(body.ordinal_of(paragraphs[2]), Some(2));
+        let span = body
+            .descendant_elements()
+            .find(|e| e.is_named("span"))
+            .expect("span should exist");
+        assert_eq!(body.ordinal_of(span), Some(0));
+        assert_eq!(body.ordinal_of(body), None);
+    }
+
+    #[test]
+    fn test_script_raw_text() {
+        let html = "<head><script type=\"text/template\"><p>{{ a < b }}</p></script><style>p > a { }</style><script>x</script></head>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(head (script[type=\"text/template\"] #text(<p>{{ a < b }}</p>)) (style #text(p > a { })) (script #text(x)))"
+        );
+        let NodeKind::Element(head) = &nodes[0].kind else {
+            panic!("Expected head, got: {:?}", &nodes[0].kind);
+        };
+        let flags = head
+            .child_elements()
+            .map(|e| e.is_data_block())
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![true, false, false]);
+
+        // without an end tag the rest of the input is script
+        let nodes = Parser::new("<script>a</div>b").parse();
+        assert_eq!(sexp(&nodes[0]), "(script #text(a</div>b))");
+    }
+
+    #[test]
+    fn test_obsolete_raw_text() {
+        let html = "<xmp><b>not bold</b></xmp><b>bold</b><LISTING>a</xmp> <p>\n</listing>";
+        let nodes = Parser::new(html).parse();
+        let rendered = nodes.iter().map(|n| sexp(n)).collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                "(xmp #text(<b>not bold</b>))",
+                "(b #text(bold))",
+                "(LISTING #text(a</xmp> <p>\n))",
+            ]
+        );
+        assert!(nodes.iter().all(|node| match &node.kind {
+            NodeKind::Element(element) => element.is_closed(),
+            _ => false,
+        }));
+        assert_eq!(nodes[0].to_html(), "<xmp><b>not bold</b></xmp>");
+    }
+
+    #[test]
+    fn test_escapable_raw_text() {
+        let html = "<title>a <b> &amp; c</TITLE><textarea><p>x</Textarea><svg><title><b>t</b></title></svg>";
+        let nodes = Parser::new(html).parse();
+        let rendered = nodes.iter().map(|n| sexp(n)).collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                "(title #text(a <b> &amp; c))",
+                "(textarea #text(<p>x))",
+                "(svg (title (b #text(t))))",
+            ]
+        );
+
+        // `<` in a script isn't reported in strict mode
+        let options = ParserOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("<script>if (a < b) {}</script>", options);
+        assert_eq!(sexp(&parser.parse()[0]), "(script #text(if (a < b) {}))");
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_find_text() {
+        let html = "<div><p>a cat</p><!-- cat --><span title=cat>my <b>ca</b>t cat</span></div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let found = div
+            .find_text("cat")
+            .iter()
+            .map(|span| (span.source(), span.byte_range()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![("cat", 10..13), ("cat", 59..62)]);
+        assert_eq!(&html[59..62], "cat");
+        assert!(div.find_text("").is_empty());
+        assert!(div.find_text("dog").is_empty());
+    }
+
+    #[test]
+    fn test_attribute_without_space_after_tag_name() {
+        let nodes = Parser::new("<input type=text>").parse();
+        assert_eq!(sexp(&nodes[0]), "(input[type=\"text\"] )");
+        let nodes = Parser::new("<inputtype=text>").parse();
+        assert_eq!(sexp(&nodes[0]), "(inputtype )");
+    }
+
+    #[test]
+    fn test_normalize_case() {
+        let html = "<DIV Class=\"x\" DATA-Id=1><svg viewBox=\"0 0 1 1\"><clipPath ClipPathUnits=x /></svg></DIV>";
+        for (normalize_tag_case, normalize_attr_case, expected) in [
+            (
+                false,
+                false,
+                "(DIV[Class=\"x\"] [DATA-Id=\"1\"] (svg[viewBox=\"0 0 1 1\"] (clipPath[ClipPathUnits=\"x\"] )))",
+            ),
+            (
+                true,
+                false,
+                "(div[Class=\"x\"] [DATA-Id=\"1\"] (svg[viewBox=\"0 0 1 1\"] (clipPath[ClipPathUnits=\"x\"] )))",
+            ),
+            (
+                false,
+                true,
+                "(DIV[class=\"x\"] [data-id=\"1\"] (svg[viewBox=\"0 0 1 1\"] (clipPath[ClipPathUnits=\"x\"] )))",
+            ),
+            (
+                true,
+                true,
+                "(div[class=\"x\"] [data-id=\"1\"] (svg[viewBox=\"0 0 1 1\"] (clipPath[ClipPathUnits=\"x\"] )))",
+            ),
+        ] {
+            let options = ParserOptions {
+                normalize_tag_case,
+                normalize_attr_case,
+                ..Default::default()
+            };
+            let nodes = Parser::with_options(html, options).parse();
+            assert_eq!(sexp(&nodes[0]), expected);
+            let NodeKind::Element(div) = &nodes[0].kind else {
+                panic!("Expected div, got: {:?}", &nodes[0].kind);
+            };
+            // spans still point at the source as written
+            assert_eq!(div.tag_name.span().source(), "DIV");
+            assert_eq!(div.attr("data-id"), Some("1"));
+        }
+    }
+
+    #[test]
+    fn test_value_percent_decoded() {
+        let html = "<a href=\"/my%20file%zz%2.pdf?q=caf%C3%A9&x=%+5%-1%\" title=plain></a>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(a) = &nodes[0].kind else {
+            panic!("Expected a, got: {:?}", &nodes[0].kind);
+        };
+        let decoded = a
+            .attributes
+            .iter()
+            .map(|attribute| attribute.value_percent_decoded())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded[0], "/my file%zz%2.pdf?q=café&x=%+5%-1%");
+        assert!(matches!(decoded[1], Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn test_whitespace_around_equals() {
+        let nodes = Parser::new("<a href =\"x\" title = \"y\" download>link</a>").parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(a[href=\"x\"] [title=\"y\"] [download=\"\"] #text(link))"
+        );
+    }
+
+    #[test]
+    fn test_no_break_space() {
+        let html = "\u{a0}<p title=\"\u{a0}\">\u{a0}</p>\u{a0}";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(sexp(&nodes[1]), "(p[title=\"\u{a0}\"] #text(\u{a0}))");
+        assert_eq!(sexp(&nodes[2]), "#text(\u{a0})");
+    }
+
+    #[test]
+    fn test_parse_available() {
+        let html = "<p>one</p><!-- c -->\n<p>two</p><br/><div cla";
+        let (nodes, offset) = Parser::new(html).parse_available();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(sexp(&nodes[4]), "(br )");
+        assert_eq!(&html[..offset], "<p>one</p><!-- c -->\n<p>two</p><br/>");
+
+        for (html, expected) in [
+            ("<p>one</p>tail", "<p>one</p>"),
+            ("<p>one</p><ul><li>a</li>", "<p>one</p>"),
+            ("<p>one</p><p>two</p", "<p>one</p>"),
+            ("<p>one</p><p>two</p>", "<p>one</p><p>two</p>"),
+            ("  <p>one", ""),
+        ] {
+            let (_, offset) = Parser::new(html).parse_available();
+            assert_eq!(&html[..offset], expected, "{html}");
+        }
+    }
+
+    #[test]
+    fn test_unterminated_quoted_value() {
+        let html = "<p>link: <a href=\"unterminated";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(p #text(link: ) (a[href=\"unterminated\"] ))"
+        );
+    }
+
+    #[test]
+    fn test_effective_lang() {
+        let html = "<html lang=\"en\"><body><p>hi <q lang=fr>salut</q></p><div lang=\"\"><i>?</i></div></body></html>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(root) = &nodes[0].kind else {
+            panic!("Expected html, got: {:?}", &nodes[0].kind);
+        };
+        let find = |name: &str| {
+            root.descendant_elements()
+                .find(|e| e.is_named(name))
+                .expect("element should exist")
+        };
+        assert_eq!(find("p").effective_lang(), Some("en"));
+        assert_eq!(find("q").effective_lang(), Some("fr"));
+        assert_eq!(root.effective_lang(), Some("en"));
+        assert_eq!(find("i").effective_lang(), None);
+        assert_eq!(find("body").effective_lang(), Some("en"));
+    }
+
+    #[test]
+    fn test_effective_dir() {
+        let html = "<html><body dir=rtl><p>שלום <bdi dir=ltr>hi</bdi> <span dir=sideways>x</span></p><pre dir=AUTO>?</pre></body></html>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(root) = &nodes[0].kind else {
+            panic!("Expected html, got: {:?}", &nodes[0].kind);
+        };
+        let find = |name: &str| {
+            root.descendant_elements()
+                .find(|e| e.is_named(name))
+                .expect("element should exist")
+        };
+        assert_eq!(find("p").effective_dir(), Some("rtl"));
+        assert_eq!(find("bdi").effective_dir(), Some("ltr"));
+        assert_eq!(find("span").effective_dir(), Some("rtl"));
+        assert_eq!(find("pre").effective_dir(), Some("AUTO"));
+        assert_eq!(root.effective_dir(), None);
+    }
+
+    #[test]
+    fn test_node_descendants() {
+        let html = "<div><p>a<b>b</b></p><!--c--><ul><li>d</li></ul></div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            nodes[0]
+                .descendants()
+                .map(|node| match &node.kind {
+                    NodeKind::Element(element) => element.tag_name(),
+                    NodeKind::Text(token) => token.span().source(),
+                    NodeKind::Comment(comment) => comment.text(),
+                    _ => "?",
+                })
+                .collect::<Vec<_>>(),
+            vec!["div", "p", "a", "b", "b", "c", "ul", "li", "d"]
+        );
+        let text = Parser::new("text").parse();
+        assert_eq!(text[0].descendants().count(), 1);
+    }
+
+    #[test]
+    fn test_dataset() {
+        let html = "<div id=x data-user-id=\"5\" DATA-Role=admin data-x-2=two data-=empty dataé=x data-user-id=6></div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            div.dataset(),
+            HashMap::from([("user-id", "5"), ("Role", "admin"), ("x-2", "two")])
+        );
+        assert_eq!(
+            div.dataset_camel_case(),
+            HashMap::from([
+                ("userId".to_string(), "5"),
+                ("Role".to_string(), "admin"),
+                ("x-2".to_string(), "two"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cdata_only_in_foreign_content() {
+        let html =
+            "<p><![CDATA[x<y]]></p><svg><![CDATA[x<y]]><g><![CDATA[]]></g></svg><![CDATA[z]]>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            nodes.iter().map(|node| sexp(node)).collect::<Vec<_>>(),
+            vec![
+                "(p /*[CDATA[x<y]]*/)",
+                "(svg #cdata(x<y) (g #cdata()))",
+                "/*[CDATA[z]]*/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cdata_with_brackets() {
+        let html = "<svg><text><![CDATA[a[0] ]] ]>b]]]></text><![CDATA[x]]</svg>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(svg (text #cdata(a[0] ]] ]>b])) #cdata(x]]</svg>))"
+        );
+        let NodeKind::Element(svg) = &nodes[0].kind else {
+            panic!("Expected svg, got: {:?}", &nodes[0].kind);
+        };
+        let NodeKind::CData(token) = &svg.children()[1].kind else {
+            panic!("Expected CDATA, got: {:?}", &svg.children()[1].kind);
+        };
+        assert_eq!(token.kind(), &TokenKind::CData { text: "x]]</svg>" });
+    }
+
+    #[test]
+    fn test_processing_instructions() {
+        let html = "<?xml version=\"1.0\"?>\n<rss><?xml-stylesheet href=\"s.xsl\"?><p>a<?b c?></p><?not closed";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            nodes.iter().map(|node| sexp(node)).collect::<Vec<_>>(),
+            vec![
+                "#pi(xml version=\"1.0\")",
+                "#text(\n)",
+                "(rss #pi(xml-stylesheet href=\"s.xsl\") (p #text(a) #pi(b c)) #pi(not closed))",
+            ]
+        );
+        let NodeKind::ProcessingInstruction(token) = &nodes[0].kind else {
+            panic!(
+                "Expected a processing instruction, got: {:?}",
+                &nodes[0].kind
+            );
+        };
+        assert_eq!(
+            token.kind(),
+            &TokenKind::ProcessingInstruction {
+                target: "xml",
+                data: "version=\"1.0\""
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filtered() {
+        let html = "<!DOCTYPE html><html><body><!-- c --><p>intro <div id=a>x<span>y</span></div></p>
+            <section>text<DIV id=b><div id=c>z</div></DIV><img src=i.png></section><div id=d></body></html>";
+        let nodes = Parser::new(html).parse_filtered(|name, _| name.eq_ignore_ascii_case("div"));
+        assert_eq!(
+            nodes.iter().map(|node| sexp(node)).collect::<Vec<_>>(),
+            vec![
+                "(div[id=\"a\"] #text(x) (span #text(y)))",
+                "(DIV[id=\"b\"] (div[id=\"c\"] #text(z)))",
+                "(div[id=\"d\"] )",
+            ]
+        );
+
+        let nodes = Parser::new(html)
+            .parse_filtered(|_, attributes| attributes.iter().any(|a| a.name_text() == "src"));
+        assert_eq!(sexp(&nodes[0]), "(img[src=\"i.png\"] )");
+        assert_eq!(nodes.len(), 1);
+        assert!(Parser::new(html).parse_filtered(|_, _| false).is_empty());
+        // everything but the doctype, which isn't an element
+        assert_eq!(
+            Parser::new(html).parse_filtered(|_, _| true),
+            Parser::new(html).parse()[1..]
+        );
+
+        // the filter sees names as rewritten
+        let options = ParserOptions {
+            tag_rewrite: Some(|name| if name == "section" { "div" } else { name }),
+            ..Default::default()
+        };
+        let nodes = Parser::with_options(html, options).parse_filtered(|name, _| name == "div");
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1].span().start(), (1, 12));
+        assert!(sexp(&nodes[1]).starts_with("(div #text(text) (DIV"));
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let nodes = Parser::new("<p>a<!-- b <i>c</i>").parse();
+        assert_eq!(sexp(&nodes[0]), "(p #text(a) /* b <i>c</i>*/)");
+        let (nodes, offset) = Parser::new("<p>a</p><!-- b").parse_available();
+        assert_eq!((nodes.len(), offset), (1, 8));
+    }
+
+    #[test]
+    fn test_public_accessors() {
+        let nodes = Parser::new("<UL class=menu><li>a</li><li>b</li></UL>").parse();
+        let NodeKind::Element(list) = nodes[0].kind() else {
+            panic!("Expected ul, got: {:?}", nodes[0].kind());
+        };
+        assert_eq!(list.tag_name(), "UL");
+        assert_eq!(list.attributes().len(), 1);
+        assert_eq!(list.attributes()[0].name_text(), "class");
+        assert_eq!(list.attributes()[0].value_text(), "menu");
+        assert_eq!(list.children().len(), 2);
+        assert!(
+            matches!(list.children()[1].kind(), NodeKind::Element(li) if li.tag_name() == "li")
+        );
+    }
+
+    #[test]
+    fn test_equality_ignores_spans() {
+        let a = Parser::new("<p class=x>a<!--c--><b>b</b></p>").parse();
+        let b = Parser::new("\n\n  <p class=\"x\">a<!--c--><b>b</b></p>").parse();
+        assert_eq!(a, b);
+        let c = Parser::new("<p class=y>a<!--c--><b>b</b></p>").parse();
+        assert_ne!(a, c);
+        let d = Parser::new("<p class=x>a<!--c--><b>b </b></p>").parse();
+        assert_ne!(a, d);
+        let e = Parser::new("<p class=x>a<!--d--><b>b</b></p>").parse();
+        assert_ne!(a, e);
+        let html = "<ul><li>x</li><li>x</li></ul>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(list) = &nodes[0].kind else {
+            panic!("Expected ul, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(list.children[0], list.children[1]);
+    }
+
+    #[test]
+    fn test_elements_by_tag_with_depth() {
+        let html = "<ul><li>a<ul><li>b<ol><LI>c</LI></ol></li></ul></li><li>d</li></ul>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(list) = &nodes[0].kind else {
+            panic!("Expected ul, got: {:?}", &nodes[0].kind);
+        };
+        let items = list
+            .elements_by_tag_with_depth("li")
+            .map(|(depth, li)| (depth, &li.inner_source()[..1]))
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![(1, "a"), (3, "b"), (5, "c"), (1, "d")]);
+    }
+
+    #[test]
+    fn test_outer_source() {
+        let html = "<ul>\n  <li   class = 'a'\tdata-x=1 >one</li ><LI\nhidden/>\n</ul>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(list) = &nodes[0].kind else {
+            panic!("Expected ul, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(list.outer_source(), html);
+        let items = list.descendant_elements().collect::<Vec<_>>();
+        assert_eq!(
+            items[0].outer_source(),
+            "<li   class = 'a'\tdata-x=1 >one</li >"
+        );
+        assert_eq!(items[0].start_tag_source(), "<li   class = 'a'\tdata-x=1 >");
+        assert_eq!(items[1].outer_source(), "<LI\nhidden/>");
+        // to_html normalizes the tags
+        assert_eq!(items[0].to_html(), "<li class=\"a\" data-x=\"1\">one</li>");
+    }
+
+    #[test]
+    fn test_text_content() {
+        let html = "<article><h1>Title</h1>\n<p>Fish &amp; <b>chips</b><!-- not text --></p><script>var x = 1;</script><style>p {}</style><svg><text><![CDATA[a<b]]></text></svg><textarea>typed</textarea></article>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(article) = &nodes[0].kind else {
+            panic!("Expected article, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(article.text_content(), "Title\nFish &amp; chipsa<btyped");
+        let NodeKind::Element(script) = &article.children()[3].kind else {
+            panic!("Expected script, got: {:?}", &article.children()[3].kind);
+        };
+        // only the content of nested scripts is left out
+        assert_eq!(script.text_content(), "var x = 1;");
+    }
+
+    #[test]
+    fn test_classes() {
+        let html = "<p class=\" note\tbig  Note \">x</p><p class=\"\">y</p><p>z</p>";
+        let nodes = Parser::new(html).parse();
+        let paragraphs = nodes
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Element(element) => Some(element),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paragraphs[0].classes().collect::<Vec<_>>(),
+            vec!["note", "big", "Note"]
+        );
+        assert!(paragraphs[0].has_class("big"));
+        assert!(!paragraphs[0].has_class("bi"));
+        assert!(!paragraphs[0].has_class("BIG"));
+        assert_eq!(paragraphs[1].classes().count(), 0);
+        assert_eq!(paragraphs[2].classes().count(), 0);
+        assert!(!paragraphs[2].has_class("note"));
+    }
+
+    #[test]
+    fn test_word_count() {
+        let html = "<article>
+            <h1>A short  title</h1>
+            <p>First line<br>second <b>line</b>, with <i>in</i>line tags.</p>
+            <script>var words = 'not counted at all';</script>
+            <style>p { color: red }</style>
+        </article>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(article) = &nodes[0].kind else {
+            panic!("Expected article, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(article.word_count(), 10);
+
+        let nodes = Parser::new("<p> <br> </p>").parse();
+        let NodeKind::Element(p) = &nodes[0].kind else {
+            panic!("Expected p, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(p.word_count(), 0);
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let element = |html| match Parser::with_options(
+            html,
+            ParserOptions {
+                keep_duplicate_attributes: true,
+                ..Default::default()
+            },
+        )
+        .parse()
+        .remove(0)
+        .kind
+        {
+            NodeKind::Element(element) => element,
+            kind => panic!("Expected an element, got: {kind:?}"),
+        };
+        let a = element("<div id=x class=\"a b\"><p title=t lang=en>hi <b>there</b></p></div>");
+        let b = element("<DIV class='a b' ID=x><p lang=en title=\"t\">hi <B>there</b></p></div>");
+        assert!(a.semantic_eq(&b));
+        assert!(b.semantic_eq(&a));
+        assert_ne!(a, b);
+        // the first of duplicate attributes counts
+        assert!(a.semantic_eq(&element(
+            "<div id=x class=\"a b\" id=y><p title=t lang=en>hi <b>there</b></p></div>"
+        )));
+
+        for different in [
+            "<div id=x class=\"b a\"><p title=t lang=en>hi <b>there</b></p></div>",
+            "<div id=x class=\"a b\"><p title=t>hi <b>there</b></p></div>",
+            "<div id=x class=\"a b\"><p title=t lang=en>hi <i>there</i></p></div>",
+            "<div id=x class=\"a b\"><p title=t lang=en>hi  <b>there</b></p></div>",
+            "<div id=x class=\"a b\"><p title=t lang=en>hi <b>there</b></p><br></div>",
+            "<section id=x class=\"a b\"><p title=t lang=en>hi <b>there</b></p></section>",
+        ] {
+            assert!(!a.semantic_eq(&element(different)), "{different}");
+        }
+        // names in foreign content are case-sensitive
+        assert!(!element("<svg viewBox=1></svg>").semantic_eq(&element("<svg viewbox=1></svg>")));
+    }
+
+    #[test]
+    fn test_tag_rewrite() {
+        let html = "<center id=c><p>a</p><Center>b</CENTER></center><font>x</font>";
+        let options = ParserOptions {
+            tag_rewrite: Some(|name| {
+                if name.eq_ignore_ascii_case("center") {
+                    "div"
+                } else {
+                    name
+                }
+            }),
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let nodes = parser.parse();
+        assert_eq!(
+            nodes.iter().map(|node| sexp(node)).collect::<Vec<_>>(),
+            vec![
+                "(div[id=\"c\"] (p #text(a)) (div #text(b)))",
+                "(font #text(x))"
+            ]
+        );
+        let NodeKind::Element(center) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(center.name(), "div");
+        assert_eq!(center.tag_name(), "center");
+        assert!(center.is_named("div"));
+        assert_eq!(
+            center.outer_source(),
+            "<center id=c><p>a</p><Center>b</CENTER></center>"
+        );
+        assert_eq!(center.to_html(), "<div id=\"c\"><p>a</p><div>b</div></div>");
+        // only `<font>` is still obsolete
+        assert_eq!(
+            parser
+                .diagnostics()
+                .iter()
+                .map(|d| (d.code(), d.span().source()))
+                .collect::<Vec<_>>(),
+            vec![("deprecated-element", "<font>")]
+        );
+    }
+
+    #[test]
+    fn test_has_descendant() {
+        let html = "<section><h2>Contact</h2><div><form action=/send><input name=q></form></div></section>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(section) = &nodes[0].kind else {
+            panic!("Expected section, got: {:?}", &nodes[0].kind);
+        };
+        assert!(section.has_descendant(|e| e.is_named("form")));
+        assert!(section.has_descendant(|e| e.attr("name") == Some("q")));
+        assert!(!section.has_descendant(|e| e.is_named("section")));
+        assert!(!section.has_descendant(|e| e.is_named("table")));
+    }
+
+    #[test]
+    fn test_is_self_closing() {
+        for (html, expected, self_closing) in [
+            ("<br/>", "(br )", true),
+            ("<div a/>x", "(div[a=\"\"] )", true),
+            ("<a href=/>x</a>", "(a[href=\"/\"] #text(x))", false),
+            ("<p / >y</p>", "(p #text(y))", false),
+            ("<p>y</p>", "(p #text(y))", false),
+        ] {
+            let nodes = Parser::new(html).parse();
+            assert_eq!(sexp(&nodes[0]), expected);
+            let NodeKind::Element(element) = &nodes[0].kind else {
+                panic!("Expected an element, got: {:?}", &nodes[0].kind);
+            };
+            assert_eq!((html, element.is_self_closing()), (html, self_closing));
+        }
+    }
+
+    #[test]
+    fn test_void_elements() {
+        let html = "<p>a<br>b<BR>c<img src=x.png><Input type=checkbox> d<hr></p><wbr>e";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(
+            nodes.iter().map(|n| sexp(n)).collect::<Vec<_>>(),
+            vec![
+                "(p #text(a) (br ) #text(b) (BR ) #text(c) (img[src=\"x.png\"] ) (Input[type=\"checkbox\"] ) #text( d) (hr ))",
+                "(wbr )",
+                "#text(e)",
+            ]
+        );
+        // a void element in foreign content has content like any other
+        let nodes = Parser::new("<svg><image>x</image></svg>").parse();
+        assert_eq!(sexp(&nodes[0]), "(svg (image #text(x)))");
+        let nodes = Parser::new("<svg><img>x</img></svg>").parse();
+        assert_eq!(sexp(&nodes[0]), "(svg (img #text(x)))");
+
+        let (nodes, end) = Parser::new("<p>a</p><br><br").parse_available();
+        assert_eq!((nodes.len(), end), (2, 12));
+    }
+
+    #[test]
+    fn test_deprecated_element_diagnostic() {
+        let html = "<div>\n  <center>old</center>\n</div>";
+        let options = ParserOptions {
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        parser.parse();
+        let diagnostics = parser.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), "deprecated-element");
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+        assert_eq!(diagnostics[0].span().source(), "<center>");
+        assert_eq!(diagnostics[0].span().range().start.line, 1);
+        assert_eq!(diagnostics[0].span().range().start.column, 2);
+
+        // nothing is collected unless asked for
+        let mut parser = Parser::new(html);
+        parser.parse();
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_max_attributes_per_element() {
+        let html = "<div a=1 b=2 c=3 d=4><img w=1 h=2 alt=x/></div>";
+        let options = ParserOptions {
+            diagnostics: true,
+            max_attributes_per_element: Some(2),
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let nodes = parser.parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(div[a=\"1\"] [b=\"2\"] (img[w=\"1\"] [h=\"2\"] ))"
+        );
+        let diagnostics = parser
+            .diagnostics()
+            .iter()
+            .map(|d| (d.code(), d.span().source()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![("too-many-attributes", "c"), ("too-many-attributes", "alt")]
+        );
+    }
+
+    #[test]
+    fn test_attr_exact() {
+        let html = "<el Attr=\"x\" attr=\"y\"></el>";
+        // as HTML the second would be dropped as a duplicate
+        let options = ParserOptions {
+            keep_duplicate_attributes: true,
+            ..Default::default()
+        };
+        let nodes = Parser::with_options(html, options).parse();
+        let NodeKind::Element(element) = &nodes[0].kind else {
+            panic!("Expected el, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(element.attr_exact("Attr"), Some("x"));
+        assert_eq!(element.attr_exact("attr"), Some("y"));
+        assert_eq!(element.attr_exact("ATTR"), None);
+        assert_eq!(element.attr("ATTR"), Some("x"));
+    }
+
+    #[test]
+    fn test_get_attribute() {
+        let html = "<a Href='/x' title=\"t\" data-n=3><svg viewBox=\"0 0 1 1\"></svg>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(element) = &nodes[0].kind else {
+            panic!("Expected a, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(element.get_attribute("title"), Some("t"));
+        assert_eq!(element.get_attribute("data-n"), Some("3"));
+        assert_eq!(element.get_attribute("href"), Some("/x"));
+        assert_eq!(element.get_attribute("HREF"), Some("/x"));
+        assert_eq!(element.get_attribute("alt"), None);
+        let NodeKind::Element(svg) = &element.children()[0].kind else {
+            panic!("Expected svg, got: {:?}", &element.children()[0].kind);
+        };
+        assert_eq!(svg.get_attribute("viewBox"), Some("0 0 1 1"));
+        assert_eq!(svg.get_attribute("viewbox"), None);
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        let html = "<div id=\"a\" class=x ID=\"b\" title=t id=\"c\"></div><svg viewBox=\"0\" viewbox=\"1\" viewBox=\"2\"></svg>";
+        let options = ParserOptions {
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let nodes = parser.parse();
+        assert_eq!(
+            sexp(&nodes[0]),
+            "(div[id=\"a\"] [class=\"x\"] [title=\"t\"] )"
+        );
+        assert_eq!(sexp(&nodes[1]), "(svg[viewBox=\"0\"] [viewbox=\"1\"] )");
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            div.raw_attributes()
+                .iter()
+                .map(|a| (a.name_text(), a.value_text()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("id", "a"),
+                ("class", "x"),
+                ("ID", "b"),
+                ("title", "t"),
+                ("id", "c")
+            ]
+        );
+        assert_eq!(
+            parser
+                .diagnostics()
+                .iter()
+                .map(|d| (d.code(), d.span().source()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("duplicate-attribute", "ID"),
+                ("duplicate-attribute", "viewBox"),
+            ]
+        );
+
+        // duplicates count towards the attribute limit
+        let repeated = format!("<div a=1{}b=2>", " a".repeat(1000));
+        let options = ParserOptions {
+            max_attributes_per_element: Some(3),
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(&repeated, options);
+        let nodes = parser.parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(div.attributes().len(), 1);
+        assert_eq!(div.raw_attributes().len(), 3);
+        assert_eq!(
+            parser
+                .diagnostics()
+                .iter()
+                .map(|d| d.code())
+                .collect::<Vec<_>>(),
+            vec!["duplicate-attribute", "too-many-attributes"]
+        );
+
+        let options = ParserOptions {
+            keep_duplicate_attributes: true,
+            ..Default::default()
+        };
+        let nodes = Parser::with_options(html, options).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(div.attributes().len(), 5);
+        assert_eq!(div.raw_attributes().len(), 5);
+        assert_eq!(div.attr("id"), Some("a"));
+    }
+
+    #[test]
+    fn test_attribute_name_eq_ignore_ascii_case() {
+        let html = "<a HREF=\"/Path\" Data-X=1>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(element) = &nodes[0].kind else {
+            panic!("Expected a, got: {:?}", &nodes[0].kind);
+        };
+        let href = &element.attributes()[0];
+        assert!(href.name_eq_ignore_ascii_case("href"));
+        assert!(href.name_eq_ignore_ascii_case("HREF"));
+        assert!(!href.name_eq_ignore_ascii_case("hre"));
+        assert_eq!(href.name_text(), "HREF");
+        assert!(element.attributes()[1].name_eq_ignore_ascii_case("data-x"));
+        // values keep their case
+        assert_eq!(element.attr("href"), Some("/Path"));
+    }
+
+    #[test]
+    fn test_self_closing_without_space() {
+        for (html, name, attributes) in [
+            ("<br/>after", "br", vec![]),
+            ("<input type=\"x\"/>after", "input", vec![("type", "x")]),
+            ("<input disabled/>after", "input", vec![("disabled", "")]),
+            ("<div/>after", "div", vec![]),
+        ] {
+            let nodes = Parser::new(html).parse();
+            assert_eq!(nodes.len(), 2, "{html}");
+            let NodeKind::Element(element) = &nodes[0].kind else {
+                panic!("Expected {name}, got: {:?}", &nodes[0].kind);
+            };
+            assert_eq!(element.tag_name.kind(), &TokenKind::TagName { name });
+            assert_eq!(
+                element
+                    .attributes
+                    .iter()
+                    .map(|a| (a.name_text(), a.value_text()))
+                    .collect::<Vec<_>>(),
+                attributes
+            );
+            assert!(element.children.is_empty());
+            assert_eq!(element.span.source(), &html[..html.len() - "after".len()]);
+            assert!(matches!(&nodes[1].kind, NodeKind::Text(_)));
+        }
+    }
+
+    #[test]
+    fn test_bare_less_than_diagnostic() {
+        let html = "<p>a < b</p>\n<p>a &lt; b</p>";
+        let options = ParserOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let nodes = parser.parse();
+        assert_eq!(sexp(&nodes[0]), "(p #text(a < b))");
+        let diagnostics = parser.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), "bare-less-than");
+        assert_eq!(diagnostics[0].severity(), Severity::Error);
+        assert_eq!(diagnostics[0].span().byte_range(), 5..6);
+        assert_eq!(diagnostics[0].span().range().start.column, 5);
+
+        // the text is the same outside strict mode, just not flagged
+        let mut parser = Parser::with_options(
+            html,
+            ParserOptions {
+                diagnostics: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(sexp(&parser.parse()[0]), "(p #text(a < b))");
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_attribute_value_without_name() {
+        let html = "<div =\"value\" id=x>text</div>";
+        let options = ParserOptions {
+            diagnostics: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        let nodes = parser.parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(sexp(&nodes[0]), "(div[id=\"x\"] #text(text))");
+        let diagnostics = parser.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), "missing-attribute-name");
+        assert_eq!(diagnostics[0].span().source(), "value");
+    }
+
+    #[test]
+    fn test_mismatched_end_tags() {
+        let html = "<div><b><i>x</b>y</span></div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(sexp(&nodes[0]), "(div (b (i #text(x))) #text(y))");
+
+        let options = ParserOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(html, options);
+        assert_eq!(parser.parse(), nodes);
+        let diagnostics = parser
+            .diagnostics()
+            .iter()
+            .map(|d| (d.code(), d.severity(), d.span().source()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                ("unclosed-element", Severity::Error, "<i>"),
+                ("stray-end-tag", Severity::Error, "</span>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unquoted_attribute_value_without_name() {
+        let html = "<div =foo bar=\"x\">text</div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(sexp(&nodes[0]), "(div[bar=\"x\"] #text(text))");
+
+        let nodes = Parser::new("<div = foo bar=x>text</div>").parse();
+        assert_eq!(sexp(&nodes[0]), "(div[bar=\"x\"] #text(text))");
+    }
+
+    #[test]
+    fn test_node_position() {
+        let html = "<p>one</p>\r\n<div>\n  <b>twö</b></div>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(nodes[0].position(), (0, 0));
+        assert_eq!(nodes[0].span().end(), (0, 10));
+        let NodeKind::Element(div) = &nodes[2].kind else {
+            panic!("Expected div, got: {:?}", &nodes[2].kind);
+        };
+        assert_eq!(nodes[2].position(), (1, 0));
+        let b = &div.children()[1];
+        assert_eq!(b.position(), (2, 2));
+        assert_eq!(b.span().end(), (2, 12));
+    }
 }
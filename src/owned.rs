@@ -0,0 +1,405 @@
+//! Trees that own their data, independent of the source they were parsed from.
+
+use crate::{Element, Node, NodeKind, PREFORMATTED_ELEMENTS, Parser, is_html_whitespace};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<OwnedNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedNode {
+    Text(String),
+    Element(OwnedElement),
+    Comment(String),
+    CData(String),
+    /// The content of a processing instruction between `<?` and `?>`.
+    ProcessingInstruction(String),
+    /// A whole `<!DOCTYPE ...>` declaration, as written.
+    Doctype(String),
+}
+
+/// Whether [`OwnedElement::normalize_whitespace`] keeps the whitespace in a
+/// `name` element as it is: in the elements the serializer never reformats,
+/// and in `code`, which browsers do collapse but which usually holds code
+/// whose layout matters.
+fn keeps_whitespace(name: &str) -> bool {
+    name.eq_ignore_ascii_case("code")
+        || PREFORMATTED_ELEMENTS
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(name))
+}
+
+/// `text` with each run of whitespace replaced by a single space.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !is_html_whitespace(c) {
+            collapsed.push(c);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    collapsed
+}
+
+impl OwnedNode {
+    /// `node` as an owned node. With `normalize` set, whitespace-only text
+    /// gives `None`.
+    fn from_node(node: &Node, normalize: bool) -> Option<Self> {
+        match &node.kind {
+            NodeKind::Text(token) => {
+                let text = token.span().source();
+                (!normalize || !text.trim_matches(is_html_whitespace).is_empty())
+                    .then(|| OwnedNode::Text(text.to_string()))
+            }
+            NodeKind::Element(element) => {
+                Some(OwnedNode::Element(element.to_owned_element(normalize)))
+            }
+            NodeKind::Comment(comment) => Some(OwnedNode::Comment(comment.text().to_string())),
+            NodeKind::CData(token) => Some(OwnedNode::CData(token.span().source().to_string())),
+            NodeKind::ProcessingInstruction(token) => Some(OwnedNode::ProcessingInstruction(
+                token.span().source().to_string(),
+            )),
+            NodeKind::Doctype(doctype) => {
+                Some(OwnedNode::Doctype(doctype.span().source().to_string()))
+            }
+        }
+    }
+}
+
+impl OwnedElement {
+    pub fn append_child(&mut self, node: OwnedNode) {
+        self.children.push(node);
+    }
+
+    pub fn prepend_child(&mut self, node: OwnedNode) {
+        self.children.insert(0, node);
+    }
+
+    /// Insert `node` so that it becomes the child at `index`. An index past
+    /// the end appends it.
+    pub fn insert_child_at(&mut self, index: usize, node: OwnedNode) {
+        self.children.insert(index.min(self.children.len()), node);
+    }
+
+    /// Parse `html` as a fragment and append the resulting nodes to this
+    /// element's children, like the DOM's `insertAdjacentHTML` with
+    /// `beforeend`.
+    pub fn append_html(&mut self, html: &str) {
+        let nodes = Parser::new(html).parse();
+        self.children.extend(
+            nodes
+                .iter()
+                .filter_map(|node| OwnedNode::from_node(node, false)),
+        );
+    }
+
+    /// Remove every attribute whose name isn't in `allowed`, comparing names
+    /// ignoring ASCII case. Descendants are left alone; see
+    /// [`OwnedElement::retain_attrs_deep`].
+    pub fn retain_attrs(&mut self, allowed: &[&str]) {
+        self.attributes
+            .retain(|(name, _)| allowed.iter().any(|a| a.eq_ignore_ascii_case(name)));
+    }
+
+    /// [`OwnedElement::retain_attrs`] on this element and all its descendants.
+    pub fn retain_attrs_deep(&mut self, allowed: &[&str]) {
+        self.retain_attrs(allowed);
+        for child in &mut self.children {
+            if let OwnedNode::Element(element) = child {
+                element.retain_attrs_deep(allowed);
+            }
+        }
+    }
+
+    /// Collapse each run of whitespace in the text of this element and its
+    /// descendants to a single space, trimming it from the start and end of
+    /// each element's content, and drop text left empty. The content of
+    /// elements whose whitespace matters, like `pre`, `textarea`, `script`
+    /// and `code`, is left alone.
+    pub fn normalize_whitespace(&mut self) {
+        if keeps_whitespace(&self.name) {
+            return;
+        }
+        for child in &mut self.children {
+            match child {
+                OwnedNode::Text(text) => *text = collapse_whitespace(text),
+                OwnedNode::Element(element) => element.normalize_whitespace(),
+                _ => {}
+            }
+        }
+        if let Some(OwnedNode::Text(text)) = self.children.first_mut() {
+            text.drain(..text.len() - text.trim_start_matches(' ').len());
+        }
+        if let Some(OwnedNode::Text(text)) = self.children.last_mut() {
+            text.truncate(text.trim_end_matches(' ').len());
+        }
+        self.children
+            .retain(|child| !matches!(child, OwnedNode::Text(text) if text.is_empty()));
+    }
+
+    /// Move all children into a new `tag` element that becomes the only
+    /// child, and return the new element so it can be given attributes.
+    pub fn wrap_inner(&mut self, tag: &str) -> &mut OwnedElement {
+        let wrapper = OwnedElement {
+            name: tag.to_string(),
+            attributes: vec![],
+            children: std::mem::take(&mut self.children),
+        };
+        self.children.push(OwnedNode::Element(wrapper));
+        match &mut self.children[0] {
+            OwnedNode::Element(wrapper) => wrapper,
+            _ => unreachable!("the wrapper was just added"),
+        }
+    }
+}
+
+impl Element<'_> {
+    /// An owned copy of this element, for comparing trees without regard to
+    /// spans and formatting: text nodes consisting only of whitespace are
+    /// left out, and names are taken as [`Element::name`] and
+    /// [`Attribute::name`](crate::Attribute::name) give them.
+    pub fn normalized(&self) -> OwnedElement {
+        self.to_owned_element(true)
+    }
+
+    fn to_owned_element(&self, normalize: bool) -> OwnedElement {
+        OwnedElement {
+            name: self.name().into_owned(),
+            attributes: self
+                .attributes()
+                .iter()
+                .map(|a| (a.name().into_owned(), a.value_text().to_string()))
+                .collect(),
+            children: self
+                .children
+                .iter()
+                .filter_map(|child| OwnedNode::from_node(child, normalize))
+                .collect(),
+        }
+    }
+}
+
+/// Build an [`OwnedElement`] concisely, mostly to compare against
+/// [`Element::normalized`] in tests.
+///
+/// An element is its name, then its attributes in brackets, then its children
+/// after `=>`, which are string literals for text and elements written the
+/// same way:
+///
+/// ```
+/// use html_parser::{OwnedNode, tree};
+///
+/// let list = tree!(ul[class = "menu"]["data-open" = true] => [
+///     li => ["one"],
+///     li => [a[href = "/two"] => ["two"]],
+///     hr,
+/// ]);
+/// assert_eq!(list.attributes[1], ("data-open".to_string(), "true".to_string()));
+/// assert!(matches!(&list.children[2], OwnedNode::Element(hr) if hr.children.is_empty()));
+/// ```
+///
+/// Attribute names that aren't identifiers are written as string literals;
+/// values can be anything implementing `ToString`.
+#[macro_export]
+macro_rules! tree {
+    (@attribute_name $name:ident) => {
+        stringify!($name)
+    };
+    (@attribute_name $name:literal) => {
+        $name
+    };
+    (@children [$($done:expr),*]) => {
+        vec![$($done),*]
+    };
+    (@children [$($done:expr),*] $text:literal $(, $($rest:tt)*)?) => {
+        $crate::tree!(@children [$($done,)* $crate::OwnedNode::Text($text.to_string())] $($($rest)*)?)
+    };
+    (@children [$($done:expr),*] $name:ident $([$($attribute:tt)*])* $(=> [$($children:tt)*])? $(, $($rest:tt)*)?) => {
+        $crate::tree!(@children [
+            $($done,)*
+            $crate::OwnedNode::Element($crate::tree!($name $([$($attribute)*])* $(=> [$($children)*])?))
+        ] $($($rest)*)?)
+    };
+    ($name:ident $([$attribute:tt = $value:expr])* $(=> [$($children:tt)*])?) => {
+        $crate::OwnedElement {
+            name: stringify!($name).to_string(),
+            attributes: vec![$((
+                $crate::tree!(@attribute_name $attribute).to_string(),
+                $value.to_string(),
+            )),*],
+            children: $crate::tree!(@children [] $($($children)*)?),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_matches_tree() {
+        let html = "<div class=\"x\">
+            <p>text</p>
+            <ul data-id=7><li>a <b>b</b></li><li></li></ul>
+        </div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let expected = tree!(div[class = "x"] => [
+            p => ["text"],
+            ul["data-id" = 7] => [
+                li => ["a ", b => ["b"]],
+                li,
+            ],
+        ]);
+        assert_eq!(div.normalized(), expected);
+    }
+
+    #[test]
+    fn normalized_without_duplicate_attributes() {
+        let nodes = Parser::new("<div id=a ID=b class=c><p class=d class=e></p></div>").parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            div.normalized(),
+            tree!(div[id = "a"][class = "c"] => [p[class = "d"]])
+        );
+    }
+
+    #[test]
+    fn tree_without_children() {
+        let element = tree!(img[src = "a.png"][alt = ""]);
+        assert_eq!(
+            element,
+            OwnedElement {
+                name: "img".to_string(),
+                attributes: vec![
+                    ("src".to_string(), "a.png".to_string()),
+                    ("alt".to_string(), String::new()),
+                ],
+                children: vec![],
+            }
+        );
+        let nodes = Parser::new("<IMG src=a.png alt=\"\">").parse();
+        let NodeKind::Element(img) = &nodes[0].kind else {
+            panic!("Expected img, got: {:?}", &nodes[0].kind);
+        };
+        assert_ne!(img.normalized(), element);
+    }
+
+    #[test]
+    fn append_html() {
+        let mut element = tree!(div[id = "box"] => ["start"]);
+        element.append_html("<span>x</span> &amp; <!--c-->");
+        let mut expected = tree!(div[id = "box"] => ["start", span => ["x"], " &amp; "]);
+        expected.children.push(OwnedNode::Comment("c".to_string()));
+        assert_eq!(element, expected);
+        assert_eq!(
+            element.to_html(),
+            "<div id=\"box\">start<span>x</span> &amp; <!--c--></div>"
+        );
+    }
+
+    #[test]
+    fn normalize_whitespace() {
+        let html = "<div>
+            <p>  some \t text\n  with <b> bold </b>  words  </p>
+            <pre>  keep
+   this </pre><p><code> a  b </code>\n</p>
+            <xmp> <b>  raw </xmp>
+        </div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let mut element = div.to_owned_element(false);
+        element.normalize_whitespace();
+        assert_eq!(
+            element,
+            tree!(div => [
+                p => ["some text with ", b => ["bold"], " words"],
+                " ",
+                pre => ["  keep\n   this "],
+                p => [code => [" a  b "]],
+                " ",
+                xmp => [" <b>  raw "],
+            ])
+        );
+    }
+
+    #[test]
+    fn wrap_inner() {
+        let mut element = tree!(div[class = "outer"] => ["a", b => ["b"], "c"]);
+        element
+            .wrap_inner("div")
+            .attributes
+            .push(("class".to_string(), "inner".to_string()));
+        assert_eq!(
+            element,
+            tree!(div[class = "outer"] => [
+                div[class = "inner"] => ["a", b => ["b"], "c"],
+            ])
+        );
+
+        let mut empty = tree!(p);
+        empty.wrap_inner("span");
+        assert_eq!(empty, tree!(p => [span]));
+    }
+
+    #[test]
+    fn insert_children() {
+        let mut list = tree!(ol => [li => ["b"], li => ["d"]]);
+        let item = |text: &str| {
+            OwnedNode::Element(OwnedElement {
+                name: "li".to_string(),
+                attributes: vec![],
+                children: vec![OwnedNode::Text(text.to_string())],
+            })
+        };
+        list.prepend_child(item("a"));
+        list.insert_child_at(2, item("c"));
+        list.insert_child_at(10, item("e"));
+        list.append_child(item("f"));
+        assert_eq!(
+            list,
+            tree!(ol => [
+                li => ["a"],
+                li => ["b"],
+                li => ["c"],
+                li => ["d"],
+                li => ["e"],
+                li => ["f"],
+            ])
+        );
+    }
+
+    #[test]
+    fn retain_attrs() {
+        let html = "<p class=intro><a HREF=\"/a\" title=A onclick=\"go()\" target=_blank>a</a>
+            <span style=\"color: red\"><a href=\"/b\" rel=nofollow>b</a></span></p>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(p) = &nodes[0].kind else {
+            panic!("Expected p, got: {:?}", &nodes[0].kind);
+        };
+        let mut shallow = p.normalized();
+        shallow.retain_attrs(&["href", "title"]);
+        assert!(shallow.attributes.is_empty());
+        assert!(matches!(&shallow.children[0], OwnedNode::Element(a) if a.attributes.len() == 4));
+
+        let mut clean = p.normalized();
+        clean.retain_attrs_deep(&["href", "title"]);
+        assert_eq!(
+            clean,
+            tree!(p => [
+                a["HREF" = "/a"][title = "A"] => ["a"],
+                span => [a[href = "/b"] => ["b"]],
+            ])
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! A small subset of XPath for addressing elements.
+
+use crate::Element;
+
+struct Step<'p> {
+    descendant: bool,
+    name: &'p str,
+    predicates: Vec<Predicate<'p>>,
+}
+
+enum Predicate<'p> {
+    HasAttribute(&'p str),
+    AttributeEquals(&'p str, &'p str),
+    Index(usize),
+}
+
+fn parse(path: &str) -> Option<Vec<Step<'_>>> {
+    let mut steps = vec![];
+    let mut rest = path.trim();
+    while !rest.is_empty() {
+        let descendant = rest.starts_with("//");
+        rest = rest.strip_prefix("//").or_else(|| rest.strip_prefix('/'))?;
+        let name_end = rest.find(['/', '[']).unwrap_or(rest.len());
+        let name = rest[..name_end].trim();
+        if name.is_empty() {
+            return None;
+        }
+        rest = &rest[name_end..];
+        let mut predicates = vec![];
+        while let Some(predicate) = rest.strip_prefix('[') {
+            let end = predicate.find(']')?;
+            predicates.push(parse_predicate(predicate[..end].trim())?);
+            rest = &predicate[end + 1..];
+        }
+        steps.push(Step {
+            descendant,
+            name,
+            predicates,
+        });
+    }
+    Some(steps)
+}
+
+fn parse_predicate(predicate: &str) -> Option<Predicate<'_>> {
+    if let Some(attribute) = predicate.strip_prefix('@') {
+        match attribute.split_once('=') {
+            Some((name, value)) => {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))?;
+                Some(Predicate::AttributeEquals(name.trim(), value))
+            }
+            None => Some(Predicate::HasAttribute(attribute.trim())),
+        }
+    } else {
+        predicate
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .map(Predicate::Index)
+    }
+}
+
+impl<'p> Step<'p> {
+    fn matches(&self, element: &Element) -> bool {
+        (self.name == "*" || element.is_named(self.name))
+            && self.predicates.iter().all(|p| match p {
+                Predicate::HasAttribute(name) => element.attr(name).is_some(),
+                Predicate::AttributeEquals(name, value) => element.attr(name) == Some(*value),
+                Predicate::Index(_) => true,
+            })
+    }
+
+    /// The children among `siblings` selected by this step.
+    fn select<'n, 'a>(&self, siblings: Vec<&'n Element<'a>>) -> Vec<&'n Element<'a>> {
+        let mut selected = siblings
+            .into_iter()
+            .filter(|e| self.matches(e))
+            .collect::<Vec<_>>();
+        for predicate in &self.predicates {
+            if let Predicate::Index(n) = predicate {
+                selected = selected.get(n - 1).into_iter().copied().collect();
+            }
+        }
+        selected
+    }
+}
+
+impl<'a> Element<'a> {
+    /// Find the elements addressed by an XPath-like `path`, in document order.
+    ///
+    /// A path is a sequence of steps, each written as `/name` (a child of the
+    /// current elements) or `//name` (a descendant of the current elements).
+    /// `name` is a tag name, compared ignoring ASCII case, or `*` for any
+    /// element. Each step can be followed by predicates:
+    ///
+    /// - `[@attr]` keeps elements that have the attribute,
+    /// - `[@attr="value"]` keeps elements whose attribute has exactly that value,
+    /// - `[n]` keeps the `n`th (1-based) element matched among the children of
+    ///   the same parent.
+    ///
+    /// The path is evaluated as if this element were the only child of a
+    /// document, so `/html/body` works when run on an `html` element. A path
+    /// that can't be parsed matches nothing.
+    pub fn query_path(&self, path: &str) -> Vec<&Element<'a>> {
+        let Some(steps) = parse(path) else {
+            return vec![];
+        };
+        // each entry is the list of children of one parent that the next step
+        // chooses from; the first parent is the virtual document around `self`
+        let mut parents: Vec<Vec<&Element<'a>>> = vec![vec![self]];
+        let mut selected = vec![];
+        for step in &steps {
+            if step.descendant {
+                parents = parents
+                    .into_iter()
+                    .flat_map(|siblings| {
+                        let nested = siblings
+                            .iter()
+                            .flat_map(|e| std::iter::once(*e).chain(e.descendant_elements()))
+                            .map(|e| e.child_elements().collect())
+                            .collect::<Vec<_>>();
+                        std::iter::once(siblings).chain(nested)
+                    })
+                    .collect();
+            }
+            selected = parents
+                .into_iter()
+                .flat_map(|siblings| step.select(siblings))
+                .collect::<Vec<_>>();
+            selected.sort_by_key(|e| e.span.byte_range().start);
+            selected.dedup_by(|a, b| std::ptr::eq(*a, *b));
+            parents = selected
+                .iter()
+                .map(|e| e.child_elements().collect())
+                .collect();
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NodeKind, Parser};
+
+    fn tags(elements: Vec<&crate::Element>) -> Vec<String> {
+        elements
+            .iter()
+            .map(|e| format!("{}{}", e.tag_name.span().source(), e.inner_source()))
+            .collect()
+    }
+
+    const PAGE: &str = "<html><body>
+        <div id=\"nav\"><a href=\"/\">home</a><a>about</a></div>
+        <div><p><a href=\"/deep\">deep</a></p><a href=\"/second\">second</a></div>
+        </body></html>";
+
+    #[test]
+    fn absolute_paths() {
+        let nodes = Parser::new(PAGE).parse();
+        let NodeKind::Element(html) = &nodes[0].kind else {
+            panic!("Expected html, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(html.query_path("/html/body/div").len(), 2);
+        assert_eq!(
+            tags(html.query_path("/html/body/div[1]/a")),
+            vec!["ahome", "aabout"]
+        );
+        assert_eq!(tags(html.query_path("/html/body/div/a[2]")), vec!["aabout"]);
+        assert!(html.query_path("/body").is_empty());
+    }
+
+    #[test]
+    fn descendant_paths_with_predicates() {
+        let nodes = Parser::new(PAGE).parse();
+        let NodeKind::Element(html) = &nodes[0].kind else {
+            panic!("Expected html, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            tags(html.query_path("//div/a[1]")),
+            vec!["ahome", "asecond"]
+        );
+        assert_eq!(
+            tags(html.query_path("//a[@href]")),
+            vec!["ahome", "adeep", "asecond"]
+        );
+        assert_eq!(
+            tags(html.query_path("//div[@id=\"nav\"]//a[@href]")),
+            vec!["ahome"]
+        );
+        assert_eq!(tags(html.query_path("//p/*")), vec!["adeep"]);
+        assert!(html.query_path("//div[0]").is_empty());
+        assert!(html.query_path("div").is_empty());
+    }
+}
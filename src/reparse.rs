@@ -0,0 +1,409 @@
+//! Reparsing only the part of a document that an edit touched.
+
+use std::ops::Range;
+
+use crate::{
+    Attribute, Comment, Doctype, Element, ElementBox, Node, NodeKind, ParentLink, Parser,
+    ParserOptions, is_complete, is_html_whitespace,
+    tokenizer::{Shift, Tokenizer, TokenizerOptions},
+};
+
+/// Whether `node` still has the same text in `source` once moved by
+/// `offset_delta`.
+fn unchanged(node: &Node, source: &str, offset_delta: isize) -> bool {
+    let span = node.span();
+    let range = span.byte_range();
+    let start = range.start.checked_add_signed(offset_delta);
+    let end = range.end.checked_add_signed(offset_delta);
+    start
+        .zip(end)
+        .and_then(|(start, end)| source.get(start..end))
+        .is_some_and(|text| text == span.source())
+}
+
+/// A copy of `node` pointing into `source`, where its text has moved as
+/// `shift` says.
+///
+/// Descendants are copied using an explicit stack, so deeply nested trees
+/// don't grow the call stack.
+fn rebased<'b>(node: &Node, source: &'b str, shift: &Shift) -> Node<'b> {
+    // the copies of the elements whose children are being copied, innermost
+    // last, each with the children left to copy
+    let mut open = vec![];
+    let mut node = node;
+    loop {
+        // go down to the first node with nothing below it left to copy
+        let mut copy = loop {
+            let kind = match &node.kind {
+                NodeKind::Element(element) => {
+                    let copy = rebased_element(element, source, shift);
+                    let mut children = element.children.iter();
+                    match children.next() {
+                        Some(child) => {
+                            open.push((copy, children));
+                            node = child;
+                            continue;
+                        }
+                        None => NodeKind::Element(ElementBox::new(copy)),
+                    }
+                }
+                NodeKind::Text(token) => NodeKind::Text(token.rebased(source, shift)),
+                NodeKind::CData(token) => NodeKind::CData(token.rebased(source, shift)),
+                NodeKind::ProcessingInstruction(token) => {
+                    NodeKind::ProcessingInstruction(token.rebased(source, shift))
+                }
+                NodeKind::Comment(comment) => NodeKind::Comment(Comment {
+                    token: comment.token.rebased(source, shift),
+                }),
+                NodeKind::Doctype(doctype) => NodeKind::Doctype(Doctype {
+                    token: doctype.token.rebased(source, shift),
+                }),
+            };
+            break Node { kind };
+        };
+        // go back up, finishing the elements whose children are all copied
+        loop {
+            let Some((mut element, mut children)) = open.pop() else {
+                return copy;
+            };
+            element.children.push(copy);
+            if let Some(child) = children.next() {
+                open.push((element, children));
+                node = child;
+                break;
+            }
+            copy = Node {
+                kind: NodeKind::Element(ElementBox::new(element)),
+            };
+        }
+    }
+}
+
+/// A copy of `element` like [`rebased`], but without its children.
+fn rebased_element<'b>(element: &Element, source: &'b str, shift: &Shift) -> Element<'b> {
+    Element {
+        attributes: element
+            .attributes
+            .iter()
+            .map(|attribute| rebased_attribute(attribute, source, shift))
+            .collect(),
+        attribute_count: element.attribute_count,
+        children: Vec::with_capacity(element.children.len()),
+        self_closing: element.self_closing,
+        lowercase_name: element.lowercase_name,
+        tag_name: element.tag_name.rebased(source, shift),
+        namespace: element.namespace,
+        span: element.span.rebased(source, shift),
+        inner: element.inner.rebased(source, shift),
+        parent: ParentLink::default(),
+    }
+}
+
+/// A copy of `attribute` pointing into `source`, moved as `shift` says.
+fn rebased_attribute<'b>(attribute: &Attribute, source: &'b str, shift: &Shift) -> Attribute<'b> {
+    let name = attribute.name.span();
+    // the raw value comes after the name, which is in the old source at a
+    // known offset
+    let after_name = attribute.raw_value.as_ptr() as usize - name.source().as_ptr() as usize;
+    let start = shift.offset(name.byte_range().start + after_name);
+    Attribute {
+        lowercase_name: attribute.lowercase_name,
+        name: attribute.name.rebased(source, shift),
+        raw_value: &source[start..start + attribute.raw_value.len()],
+        value: attribute.value.rebased(source, shift),
+    }
+}
+
+/// Parse the top level of `source` from `start` to `end`, with `seen_doctype`
+/// saying whether a doctype there would come too late. A tag or comment
+/// running past `end` is parsed whole, leaving the parser past `end`.
+fn parse_range(source: &str, start: usize, end: usize, seen_doctype: bool) -> Parser<'_> {
+    let mut tokenizer = Tokenizer::with_options(source, TokenizerOptions::default());
+    tokenizer.skip_to(start);
+    tokenizer.stop_at(end);
+    if start == 0 {
+        tokenizer.consume_whitespace();
+    }
+    let mut parser = Parser::with_tokenizer(tokenizer, ParserOptions::default());
+    parser.seen_doctype = seen_doctype;
+    parser
+}
+
+/// Parse `source`, an edited version of the source `old_tree` was parsed
+/// from, reusing the top-level elements of `old_tree` that the edit didn't
+/// touch instead of parsing them again.
+///
+/// `edit_range` is the byte range in `source` of the text that replaced part
+/// of the old source: for an insertion the inserted text, for a deletion an
+/// empty range where the text was. Everything before it must be as it was,
+/// and everything after it as it was at the end of the old source.
+///
+/// This is conservative: only whole top-level elements are reused, and
+/// everything between the last one before the edit and the first one after
+/// it is parsed again, with the default [`ParserOptions`]. If the edit could
+/// change how what follows it parses, as an element left open would, the
+/// rest of the document is parsed again too.
+pub fn reparse_region<'a>(
+    source: &'a str,
+    old_tree: &[Node],
+    edit_range: Range<usize>,
+) -> Vec<Node<'a>> {
+    let end = source.trim_end_matches(is_html_whitespace).len();
+
+    // the top-level nodes up to the last element closed before the edit
+    let prefix_len = old_tree
+        .iter()
+        .rposition(|node| match &node.kind {
+            NodeKind::Element(element) => {
+                element.span.byte_range().end <= edit_range.start && element.is_closed()
+            }
+            _ => false,
+        })
+        .map_or(0, |i| i + 1);
+    let prefix = &old_tree[..prefix_len];
+    if !prefix.iter().all(|node| unchanged(node, source, 0)) {
+        return Parser::new(source).parse();
+    }
+    let start = prefix.last().map_or(0, |node| node.span().byte_range().end);
+    let seen_doctype = prefix.iter().any(|node| match &node.kind {
+        NodeKind::Comment(_) => false,
+        NodeKind::Text(token) => !token.span().source().chars().all(is_html_whitespace),
+        _ => true,
+    });
+
+    // the old source isn't at hand, so how far the text after the edit moved
+    // is found by lining the last node up with the end of `source`, allowing
+    // for a terminator like `-->` after its span
+    let offset_delta = old_tree.last().and_then(|last| {
+        let range = last.span().byte_range();
+        (0..=3).find_map(|terminator| {
+            let delta = end as isize - terminator - range.end as isize;
+            (range.start as isize + delta >= edit_range.end as isize
+                && unchanged(last, source, delta))
+            .then_some(delta)
+        })
+    });
+    // the top-level nodes from the first element after the edit on
+    let suffix_start = offset_delta.and_then(|delta| {
+        let first = prefix_len
+            + old_tree[prefix_len..]
+                .iter()
+                .position(|node| match &node.kind {
+                    NodeKind::Element(element) => {
+                        element.span.byte_range().start as isize + delta >= edit_range.end as isize
+                    }
+                    _ => false,
+                })?;
+        let reusable = old_tree[first..].iter().all(|node| {
+            !matches!(node.kind, NodeKind::Doctype(_)) && unchanged(node, source, delta)
+        });
+        reusable.then_some((first, delta))
+    });
+
+    let shift = Shift::default();
+    let mut nodes = prefix
+        .iter()
+        .map(|node| rebased(node, source, &shift))
+        .collect::<Vec<_>>();
+    if let Some((first, offset_delta)) = suffix_start {
+        let old_start = &old_tree[first].span().range().start;
+        let region_end = old_tree[first]
+            .span()
+            .byte_range()
+            .start
+            .wrapping_add_signed(offset_delta);
+        let mut parser = parse_range(&source[..end], start, region_end, seen_doctype);
+        let (region, _) = parser.parse_nodes();
+        // the region mustn't leave anything open that the rest would continue,
+        // and mustn't end in a tag or comment that swallows the start of it
+        let new_start = parser.tokenizer.here();
+        let self_contained = new_start.byte_range().start == region_end
+            && region.last().is_none_or(|node| {
+                matches!(node.kind, NodeKind::Text(_)) || is_complete(source, node)
+            });
+        if self_contained {
+            let new_start = new_start.range().start.clone();
+            let shift = Shift {
+                offset_delta,
+                line: old_start.line,
+                line_delta: new_start.line as isize - old_start.line as isize,
+                column_delta: new_start.column as isize - old_start.column as isize,
+            };
+            nodes.extend(region);
+            nodes.extend(
+                old_tree[first..]
+                    .iter()
+                    .map(|node| rebased(node, source, &shift)),
+            );
+            return nodes;
+        }
+    }
+    let (rest, _) = parse_range(&source[..end], start, end, seen_doctype).parse_nodes();
+    nodes.extend(rest);
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every element's tag name with where it is, to compare trees including
+    /// their spans.
+    fn layout<'a>(nodes: &[Node<'a>]) -> Vec<(&'a str, Range<usize>, usize, usize)> {
+        nodes
+            .iter()
+            .flat_map(|node| node.descendants())
+            .filter_map(|node| match &node.kind {
+                NodeKind::Element(element) => {
+                    let start = &element.span.range().start;
+                    Some((
+                        element.tag_name(),
+                        element.span.byte_range(),
+                        start.line,
+                        start.column,
+                    ))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edit_inside_one_element() {
+        let old = "<DIV id=a>one</DIV>\n<DIV id=b><p>two</p></DIV>\n<DIV id=c>three <b>3</b></DIV>";
+        // parse with lowercased names, so that reused elements can be told
+        // apart from reparsed ones
+        let options = ParserOptions {
+            normalize_tag_case: true,
+            ..Default::default()
+        };
+        let old_tree = Parser::with_options(old, options).parse();
+        let new =
+            "<DIV id=a>one</DIV>\n<DIV id=b><p>tw\no!</p></DIV>\n<DIV id=c>three <b>3</b></DIV>";
+        let edit = 34..38;
+        assert_eq!(&new[edit.clone()], "w\no!");
+        let nodes = reparse_region(new, &old_tree, edit);
+
+        let fresh = Parser::new(new).parse();
+        assert_eq!(layout(&nodes), layout(&fresh));
+        let names = nodes
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Element(element) => Some(element.name()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["div", "DIV", "div"]);
+        let NodeKind::Element(last) = &nodes[4].kind else {
+            panic!("Expected div, got: {:?}", &nodes[4].kind);
+        };
+        assert_eq!(last.attr("id"), Some("c"));
+        assert_eq!(last.inner_source(), "three <b>3</b>");
+        // the copy's children link to the copy, not to the old element
+        let b = last.child_elements().next().expect("div has a child");
+        assert!(
+            b.parent()
+                .is_some_and(|parent| std::ptr::eq(parent, &**last))
+        );
+    }
+
+    #[test]
+    fn edit_leaving_an_element_open() {
+        let old = "<div>a</div><p>b</p><section>c</section>";
+        let old_tree = Parser::new(old).parse();
+        let new = "<div>a</div><p>b<span></p><section>c</section>";
+        let nodes = reparse_region(new, &old_tree, 16..22);
+        let fresh = Parser::new(new).parse();
+        assert_eq!(nodes, fresh);
+        assert_eq!(layout(&nodes), layout(&fresh));
+    }
+
+    #[test]
+    fn reused_element_with_valueless_attribute() {
+        let old = "<div hidden  class=x>a</div><p>b</p>";
+        let old_tree = Parser::new(old).parse();
+        let new = "<div hidden  class=x>a</div><p>bc</p>";
+        let nodes = reparse_region(new, &old_tree, 33..34);
+        let fresh = Parser::new(new).parse();
+        assert_eq!(nodes, fresh);
+        assert_eq!(layout(&nodes), layout(&fresh));
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        let raw_values = div
+            .attributes()
+            .iter()
+            .map(|a| a.raw_value())
+            .collect::<Vec<_>>();
+        assert_eq!(raw_values, vec!["", "x"]);
+
+        // text moved by an edit before the element
+        let new = "<h1>t</h1><div hidden  class=x>a</div><p>b</p>";
+        let nodes = reparse_region(new, &old_tree, 0..10);
+        assert_eq!(nodes, Parser::new(new).parse());
+        let NodeKind::Element(div) = &nodes[1].kind else {
+            panic!("Expected div, got: {:?}", &nodes[1].kind);
+        };
+        assert_eq!(div.attributes()[1].raw_value(), "x");
+        // the empty raw value of `hidden` is where the whitespace after it ends
+        let offset = div.attributes()[0].raw_value().as_ptr() as usize - new.as_ptr() as usize;
+        assert_eq!(offset, 23);
+    }
+
+    #[test]
+    fn edit_cutting_off_a_tag() {
+        for (old, new, edit) in [
+            ("\r\n</p><p>", "\r\n</p<p>", 5..5),
+            ("<!DOCTYPE html><p>b</p>", "<!DOCTYPE html<p>b</p>", 14..14),
+            ("<a></a><b></b>", "<a></a><b <b></b>", 7..10),
+            // the last node is an empty instruction, which lines up anywhere
+            (
+                "<br/><??><!DOCTYPE html>",
+                "<br/><??><!<?<b>DOCTYPE html>",
+                11..16,
+            ),
+        ] {
+            let old_tree = Parser::new(old).parse();
+            let nodes = reparse_region(new, &old_tree, edit);
+            let fresh = Parser::new(new).parse();
+            assert_eq!(nodes, fresh, "{new}");
+            assert_eq!(layout(&nodes), layout(&fresh), "{new}");
+        }
+    }
+
+    #[test]
+    fn reused_deeply_nested_element() {
+        let depth = 100_000;
+        let nested = "<div>".repeat(depth) + &"</div>".repeat(depth);
+        let old = format!("<p>a</p>{nested}");
+        let old_tree = Parser::new(&old).parse();
+        let new = format!("<p>ab</p>{nested}");
+        let nodes = reparse_region(&new, &old_tree, 4..5);
+        let fresh = Parser::new(&new).parse();
+        let spans = |nodes: &[Node]| {
+            nodes
+                .iter()
+                .flat_map(|node| node.descendants())
+                .map(|node| node.span().byte_range())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(&nodes).len(), depth + 2);
+        assert_eq!(spans(&nodes), spans(&fresh));
+    }
+
+    #[test]
+    fn edit_at_the_edges() {
+        let old = "<p>a</p> <p>b</p>";
+        let old_tree = Parser::new(old).parse();
+        for (new, edit) in [
+            ("<h1>x</h1><p>a</p> <p>b</p>", 0..10),
+            ("<p>a</p> <p>b</p><!-- end -->", 17..29),
+            ("<p>a</p><p>b</p>", 8..8),
+        ] {
+            let nodes = reparse_region(new, &old_tree, edit);
+            let fresh = Parser::new(new).parse();
+            assert_eq!(nodes, fresh, "{new}");
+            assert_eq!(layout(&nodes), layout(&fresh), "{new}");
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Matching elements against CSS selectors.
+
+use crate::{Element, Node, NodeKind};
+
+/// A comma-separated list of complex selectors; an element matches if any of
+/// them does.
+struct SelectorList<'s>(Vec<Complex<'s>>);
+
+/// Compound selectors joined by combinators, e.g. `ul > li a`.
+/// `combinators[i]` sits between `compounds[i]` and `compounds[i + 1]`.
+struct Complex<'s> {
+    compounds: Vec<Compound<'s>>,
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Clone, Copy)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Default)]
+struct Compound<'s> {
+    /// `None` for `*` or a compound without a type selector.
+    tag: Option<&'s str>,
+    ids: Vec<&'s str>,
+    classes: Vec<&'s str>,
+    attributes: Vec<(&'s str, Option<&'s str>)>,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Split a name off the start of `s`.
+fn take_name(s: &str) -> Option<(&str, &str)> {
+    let end = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+    (end > 0).then(|| s.split_at(end))
+}
+
+fn parse_list(selector: &str) -> Option<SelectorList<'_>> {
+    selector
+        .split(',')
+        .map(parse_complex)
+        .collect::<Option<Vec<_>>>()
+        .map(SelectorList)
+}
+
+fn parse_complex(selector: &str) -> Option<Complex<'_>> {
+    let mut compounds = vec![];
+    let mut combinators = vec![];
+    let mut rest = selector.trim();
+    loop {
+        let (compound, after) = parse_compound(rest)?;
+        compounds.push(compound);
+        let trimmed = after.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some(after_child) = trimmed.strip_prefix('>') {
+            combinators.push(Combinator::Child);
+            rest = after_child.trim_start();
+        } else if trimmed.len() < after.len() {
+            combinators.push(Combinator::Descendant);
+            rest = trimmed;
+        } else {
+            return None;
+        }
+    }
+    Some(Complex {
+        compounds,
+        combinators,
+    })
+}
+
+fn parse_compound(selector: &str) -> Option<(Compound<'_>, &str)> {
+    let mut compound = Compound::default();
+    let mut rest = selector;
+    if let Some(after) = rest.strip_prefix('*') {
+        rest = after;
+    } else if let Some((tag, after)) = take_name(rest) {
+        compound.tag = Some(tag);
+        rest = after;
+    }
+    loop {
+        if let Some(after) = rest.strip_prefix('#') {
+            let (id, after) = take_name(after)?;
+            compound.ids.push(id);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let (class, after) = take_name(after)?;
+            compound.classes.push(class);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            compound.attributes.push(parse_attribute(&after[..end])?);
+            rest = &after[end + 1..];
+        } else {
+            break;
+        }
+    }
+    // a compound has to select something
+    (rest.len() < selector.len()).then_some((compound, rest))
+}
+
+fn parse_attribute(selector: &str) -> Option<(&str, Option<&str>)> {
+    let Some((name, value)) = selector.split_once('=') else {
+        let (name, rest) = take_name(selector.trim())?;
+        return rest.is_empty().then_some((name, None));
+    };
+    let (name, rest) = take_name(name.trim())?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value);
+    Some((name, Some(value)))
+}
+
+impl Compound<'_> {
+    fn matches(&self, element: &Element) -> bool {
+        self.tag.is_none_or(|tag| element.is_named(tag))
+            && self.ids.iter().all(|id| element.attr("id") == Some(*id))
+            && self.classes.iter().all(|class| element.has_class(class))
+            && self.attributes.iter().all(|(name, value)| match value {
+                Some(value) => element.attr(name) == Some(*value),
+                None => element.attr(name).is_some(),
+            })
+    }
+}
+
+impl Complex<'_> {
+    /// Whether `element`, whose ancestors are `ancestors` (nearest last),
+    /// matches the compounds up to and including `index`.
+    fn matches_at(&self, index: usize, element: &Element, ancestors: &[&Element]) -> bool {
+        if !self.compounds[index].matches(element) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match self.combinators[index - 1] {
+            Combinator::Child => ancestors
+                .split_last()
+                .is_some_and(|(parent, rest)| self.matches_at(index - 1, parent, rest)),
+            Combinator::Descendant => (0..ancestors.len())
+                .rev()
+                .any(|i| self.matches_at(index - 1, ancestors[i], &ancestors[..i])),
+        }
+    }
+}
+
+impl SelectorList<'_> {
+    fn matches(&self, element: &Element, ancestors: &[&Element]) -> bool {
+        self.0
+            .iter()
+            .any(|complex| complex.matches_at(complex.compounds.len() - 1, element, ancestors))
+    }
+
+    /// The elements in `nodes` and their descendants that match, in document
+    /// order. `ancestors` are the elements `nodes` are nested in.
+    fn select<'n, 'a>(
+        &self,
+        nodes: &'n [Node<'a>],
+        ancestors: Vec<&'n Element<'a>>,
+    ) -> Vec<&'n Element<'a>> {
+        let base = ancestors.len();
+        let mut path = ancestors;
+        let mut selected = vec![];
+        let mut stack = nodes
+            .iter()
+            .rev()
+            .map(|node| (node, base))
+            .collect::<Vec<_>>();
+        while let Some((node, depth)) = stack.pop() {
+            let NodeKind::Element(element) = &node.kind else {
+                continue;
+            };
+            let element = &**element;
+            path.truncate(depth);
+            if self.matches(element, &path) {
+                selected.push(element);
+            }
+            path.push(element);
+            stack.extend(
+                element
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
+        }
+        selected
+    }
+}
+
+impl<'a> Element<'a> {
+    /// Find the elements nested in this one that match the CSS `selector`, in
+    /// document order.
+    ///
+    /// Supported are type (`div`), universal (`*`), id (`#main`), class
+    /// (`.card`), attribute presence (`[hidden]`) and equality
+    /// (`[type="text"]`) selectors, combined into compounds like
+    /// `input.big[required]`, joined by descendant (` `) or child (`>`)
+    /// combinators, and grouped with `,`. Combinators only look at ancestors
+    /// up to this element. A selector that can't be parsed matches nothing.
+    pub fn select(&self, selector: &str) -> Vec<&Element<'a>> {
+        parse_list(selector)
+            .map(|list| list.select(&self.children, vec![self]))
+            .unwrap_or_default()
+    }
+
+    /// The first element nested in this one that matches the CSS `selector`,
+    /// like the DOM's `querySelector`. See [`Element::select`] for the
+    /// supported syntax.
+    pub fn query_selector(&self, selector: &str) -> Option<&Element<'a>> {
+        self.select(selector).into_iter().next()
+    }
+
+    /// Every element nested in this one that matches the CSS `selector`, in
+    /// document order, like the DOM's `querySelectorAll`. The same as
+    /// [`Element::select`].
+    pub fn query_selector_all(&self, selector: &str) -> Vec<&Element<'a>> {
+        self.select(selector)
+    }
+}
+
+/// Find the elements in `nodes`, or nested in them, that match the CSS
+/// `selector`, in document order. This works on fragments with several
+/// top-level nodes; see [`Element::select`] for the supported syntax.
+pub fn select<'n, 'a>(nodes: &'n [Node<'a>], selector: &str) -> Vec<&'n Element<'a>> {
+    parse_list(selector)
+        .map(|list| list.select(nodes, vec![]))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn names<'a>(elements: Vec<&Element<'a>>) -> Vec<&'a str> {
+        elements
+            .iter()
+            .map(|e| e.attr("id").unwrap_or(e.tag_name.span().source()))
+            .collect()
+    }
+
+    #[test]
+    fn select_across_fragment() {
+        let html = "<p id=a class=\"note big\">x</p>
+            <div id=b><p id=c class=note>y</p><span><p id=d>z</p></span></div>
+            <p id=e class=\"notes\"></p>";
+        let nodes = Parser::new(html).parse();
+        assert_eq!(names(select(&nodes, "p")), vec!["a", "c", "d", "e"]);
+        assert_eq!(names(select(&nodes, ".note")), vec!["a", "c"]);
+        assert_eq!(names(select(&nodes, "p.note.big")), vec!["a"]);
+        assert_eq!(names(select(&nodes, "div p")), vec!["c", "d"]);
+        assert_eq!(names(select(&nodes, "div > p")), vec!["c"]);
+        assert_eq!(names(select(&nodes, "#e, #b")), vec!["b", "e"]);
+        assert_eq!(names(select(&nodes, "[class]")), vec!["a", "c", "e"]);
+        assert_eq!(names(select(&nodes, "[class=\"notes\"]")), vec!["e"]);
+        assert_eq!(names(select(&nodes, "span *")), vec!["d"]);
+        assert!(select(&nodes, "p >").is_empty());
+        assert!(select(&nodes, "..x").is_empty());
+    }
+
+    #[test]
+    fn select_within_element() {
+        let html = "<div id=outer><div id=inner><a id=link></a></div></div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(outer) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(names(outer.select("div")), vec!["inner"]);
+        assert_eq!(names(outer.select("#outer > div a")), vec!["link"]);
+        assert_eq!(names(outer.select("div div a")), vec!["link"]);
+    }
+
+    #[test]
+    fn query_selector() {
+        let html =
+            "<main><form><input id=q type=\"text\" class=\"field wide\"><input type=hidden hidden>
+            <button class=field>Go</button></form></main>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(main) = &nodes[0].kind else {
+            panic!("Expected main, got: {:?}", &nodes[0].kind);
+        };
+        assert_eq!(
+            names(main.query_selector_all(".field")),
+            vec!["q", "button"]
+        );
+        assert_eq!(
+            main.query_selector("form [type=\"text\"]")
+                .and_then(|e| e.attr("id")),
+            Some("q")
+        );
+        assert_eq!(
+            main.query_selector("[hidden]").and_then(|e| e.attr("type")),
+            Some("hidden")
+        );
+        assert_eq!(
+            main.query_selector("#q").map(|e| e.tag_name()),
+            Some("input")
+        );
+        assert!(main.query_selector("main").is_none());
+        assert!(main.query_selector_all("table td").is_empty());
+    }
+}
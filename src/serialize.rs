@@ -0,0 +1,672 @@
+//! Turning a parsed tree back into HTML.
+//!
+//! Text and attribute values are kept as written in the source, entities
+//! included, so serializing only escapes what would otherwise be read back as
+//! markup: `<` and `>` in text, `"` in attribute values, and any `&` that
+//! doesn't already start a character reference.
+
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use crate::{
+    Attribute, Document, Element, INLINE_FORMATTING_ELEMENTS, Node, NodeKind, OwnedElement,
+    OwnedNode, PREFORMATTED_ELEMENTS, RAW_TEXT_ELEMENTS, VOID_ELEMENTS, is_html_whitespace,
+};
+
+/// How void elements such as `<br>` are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelfClosingStyle {
+    /// `<br>`
+    #[default]
+    Html,
+    /// `<br />`, as XHTML requires.
+    Xhtml,
+    /// `<br>`, `<br/>` or `<br />`, whichever the source used.
+    Preserve,
+}
+
+/// What happens to comments when serializing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentPolicy {
+    /// Write comments as they are.
+    #[default]
+    Keep,
+    /// Leave comments out.
+    Strip,
+    /// Keep comments where they are but replace their content with
+    /// `[redacted]`.
+    Redact,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions {
+    pub self_closing_style: SelfClosingStyle,
+    pub comment_policy: CommentPolicy,
+}
+
+/// Elements besides the inline formatting ones that the pretty-printer keeps
+/// on the same line as the text around them.
+const INLINE_ELEMENTS: &[&str] = &[
+    "br", "button", "img", "input", "label", "select", "textarea", "wbr",
+];
+
+fn is_one_of(element: &Element, names: &[&str]) -> bool {
+    names.iter().any(|name| element.is_named(name))
+}
+
+/// Whether `node` can be printed on a single line along with its siblings.
+fn is_inline(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::Text(_)
+        | NodeKind::Comment(_)
+        | NodeKind::CData(_)
+        | NodeKind::ProcessingInstruction(_) => true,
+        NodeKind::Doctype(_) => false,
+        NodeKind::Element(element) => {
+            (is_one_of(element, INLINE_FORMATTING_ELEMENTS) || is_one_of(element, INLINE_ELEMENTS))
+                && element.children.iter().all(is_inline)
+        }
+    }
+}
+
+/// Whether `&` at the start of `s` begins a character reference such as
+/// `&amp;`, `&#39;` or `&#x27;`.
+fn starts_reference(s: &str) -> bool {
+    let Some(end) = s.find(';') else {
+        return false;
+    };
+    let name = &s[1..end];
+    if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else if let Some(decimal) = name.strip_prefix('#') {
+        !decimal.is_empty() && decimal.chars().all(|c| c.is_ascii_digit())
+    } else {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+}
+
+fn write_escaped<W: Write>(w: &mut W, s: &str, in_attribute: bool) -> io::Result<()> {
+    let mut written = 0;
+    for (i, c) in s.char_indices() {
+        let escaped = match c {
+            '&' if !starts_reference(&s[i..]) => "&amp;",
+            '<' if !in_attribute => "&lt;",
+            '>' if !in_attribute => "&gt;",
+            '"' if in_attribute => "&quot;",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[written..i])?;
+        w.write_all(escaped.as_bytes())?;
+        written = i + c.len_utf8();
+    }
+    w.write_all(&s.as_bytes()[written..])
+}
+
+fn write_attribute<W: Write>(w: &mut W, attribute: &Attribute) -> io::Result<()> {
+    write!(w, "{}", attribute.name())?;
+    if !attribute.value.span().source().is_empty() {
+        w.write_all(b"=\"")?;
+        write_escaped(w, attribute.value_text(), true)?;
+        w.write_all(b"\"")?;
+    }
+    Ok(())
+}
+
+fn write_start_tag<W: Write>(
+    w: &mut W,
+    element: &Element,
+    options: &SerializeOptions,
+) -> io::Result<()> {
+    write!(w, "<{}", element.name())?;
+    for attribute in element.attributes() {
+        w.write_all(b" ")?;
+        write_attribute(w, attribute)?;
+    }
+    if !is_one_of(element, VOID_ELEMENTS) {
+        return w.write_all(b">");
+    }
+    let end = match options.self_closing_style {
+        SelfClosingStyle::Html => ">",
+        SelfClosingStyle::Xhtml => " />",
+        SelfClosingStyle::Preserve if !element.is_self_closing() => ">",
+        SelfClosingStyle::Preserve => {
+            let tag = element.start_tag_source();
+            if tag[..tag.len() - 2].ends_with(is_html_whitespace) {
+                " />"
+            } else {
+                "/>"
+            }
+        }
+    };
+    w.write_all(end.as_bytes())
+}
+
+fn write_end_tag<W: Write>(w: &mut W, element: &Element) -> io::Result<()> {
+    if is_one_of(element, VOID_ELEMENTS) {
+        return Ok(());
+    }
+    write!(w, "</{}>", element.name())
+}
+
+/// Write `node` without adding or removing any whitespace, except that runs
+/// of whitespace in text become a single space when `collapse` is set.
+fn write_compact<W: Write>(
+    w: &mut W,
+    node: &Node,
+    collapse: bool,
+    options: &SerializeOptions,
+) -> io::Result<()> {
+    match &node.kind {
+        NodeKind::Text(token) => {
+            let text = token.span().source();
+            if collapse {
+                let mut words = text.split(is_html_whitespace).filter(|w| !w.is_empty());
+                if text.starts_with(is_html_whitespace) {
+                    w.write_all(b" ")?;
+                }
+                if let Some(first) = words.next() {
+                    write_escaped(w, first, false)?;
+                    for word in words {
+                        w.write_all(b" ")?;
+                        write_escaped(w, word, false)?;
+                    }
+                    if text.ends_with(is_html_whitespace) {
+                        w.write_all(b" ")?;
+                    }
+                }
+                Ok(())
+            } else {
+                write_escaped(w, text, false)
+            }
+        }
+        NodeKind::Comment(comment) => match options.comment_policy {
+            CommentPolicy::Keep => write!(w, "<!--{}-->", comment.text()),
+            CommentPolicy::Strip => Ok(()),
+            CommentPolicy::Redact => w.write_all(b"<!-- [redacted] -->"),
+        },
+        NodeKind::CData(token) => write!(w, "<![CDATA[{}]]>", token.span().source()),
+        NodeKind::ProcessingInstruction(token) => write!(w, "<?{}?>", token.span().source()),
+        NodeKind::Doctype(doctype) => w.write_all(doctype.span().source().as_bytes()),
+        NodeKind::Element(element) => write_compact_element(w, element, collapse, options),
+    }
+}
+
+/// Write `element` like [`write_compact`]. Nested elements are written using
+/// an explicit stack, so deeply nested trees don't grow the call stack.
+fn write_compact_element<W: Write>(
+    w: &mut W,
+    element: &Element,
+    collapse: bool,
+    options: &SerializeOptions,
+) -> io::Result<()> {
+    write_start_tag(w, element, options)?;
+    // the elements whose content is being written, innermost last, each with
+    // the children left to write and whether to collapse whitespace in them
+    let collapse = collapse && !is_one_of(element, PREFORMATTED_ELEMENTS);
+    let mut open = vec![(element, element.children.iter(), collapse)];
+    while let Some((element, children, collapse)) = open.last_mut() {
+        let (element, collapse) = (*element, *collapse);
+        let Some(child) = children.next() else {
+            write_end_tag(w, element)?;
+            open.pop();
+            continue;
+        };
+        match &child.kind {
+            // raw text can't contain markup, so it has nothing to escape
+            NodeKind::Text(token) if is_one_of(element, RAW_TEXT_ELEMENTS) => {
+                w.write_all(token.span().source().as_bytes())?
+            }
+            NodeKind::Element(child) => {
+                write_start_tag(w, child, options)?;
+                let collapse = collapse && !is_one_of(child, PREFORMATTED_ELEMENTS);
+                open.push((child, child.children.iter(), collapse));
+            }
+            _ => write_compact(w, child, collapse, options)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_owned_element<W: Write>(w: &mut W, element: &OwnedElement) -> io::Result<()> {
+    let is_named = |names: &[&str]| names.iter().any(|n| n.eq_ignore_ascii_case(&element.name));
+    write!(w, "<{}", element.name)?;
+    for (name, value) in &element.attributes {
+        write!(w, " {name}")?;
+        if !value.is_empty() {
+            w.write_all(b"=\"")?;
+            write_escaped(w, value, true)?;
+            w.write_all(b"\"")?;
+        }
+    }
+    w.write_all(b">")?;
+    for child in &element.children {
+        match child {
+            OwnedNode::Text(text) if is_named(RAW_TEXT_ELEMENTS) => w.write_all(text.as_bytes())?,
+            OwnedNode::Text(text) => write_escaped(w, text, false)?,
+            OwnedNode::Element(child) => write_owned_element(w, child)?,
+            OwnedNode::Comment(comment) => write!(w, "<!--{comment}-->")?,
+            OwnedNode::CData(text) => write!(w, "<![CDATA[{text}]]>")?,
+            OwnedNode::ProcessingInstruction(content) => write!(w, "<?{content}?>")?,
+            OwnedNode::Doctype(doctype) => w.write_all(doctype.as_bytes())?,
+        }
+    }
+    if is_named(VOID_ELEMENTS) {
+        return Ok(());
+    }
+    write!(w, "</{}>", element.name)
+}
+
+struct Pretty<'w, W> {
+    out: &'w mut W,
+    indent: usize,
+    options: &'w SerializeOptions,
+}
+
+impl<W: Write> Pretty<'_, W> {
+    fn pad(&mut self, depth: usize) -> io::Result<()> {
+        write!(self.out, "{:width$}", "", width = depth * self.indent)
+    }
+
+    /// Write a run of inline siblings as one trimmed line, if it has any content.
+    fn line(&mut self, nodes: &[Node], depth: usize) -> io::Result<()> {
+        let mut line = vec![];
+        for node in nodes {
+            write_compact(&mut line, node, true, self.options)?;
+        }
+        let line = String::from_utf8(line).expect("serialized HTML is UTF-8");
+        let line = line.trim_matches(is_html_whitespace);
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.pad(depth)?;
+        writeln!(self.out, "{line}")
+    }
+
+    fn element(&mut self, element: &Element, depth: usize) -> io::Result<()> {
+        if is_one_of(element, PREFORMATTED_ELEMENTS) {
+            self.pad(depth)?;
+            write_compact_element(self.out, element, false, self.options)?;
+            return writeln!(self.out);
+        }
+        if element.children.iter().all(is_inline) {
+            self.pad(depth)?;
+            write_start_tag(self.out, element, self.options)?;
+            let mut content = vec![];
+            for child in &element.children {
+                write_compact(&mut content, child, true, self.options)?;
+            }
+            let content = String::from_utf8(content).expect("serialized HTML is UTF-8");
+            self.out
+                .write_all(content.trim_matches(is_html_whitespace).as_bytes())?;
+            write_end_tag(self.out, element)?;
+            return writeln!(self.out);
+        }
+        self.pad(depth)?;
+        write_start_tag(self.out, element, self.options)?;
+        writeln!(self.out)?;
+        let mut run_start = 0;
+        for (i, child) in element.children.iter().enumerate() {
+            if let NodeKind::Element(child_element) = &child.kind
+                && !is_inline(child)
+            {
+                self.line(&element.children[run_start..i], depth + 1)?;
+                self.element(child_element, depth + 1)?;
+                run_start = i + 1;
+            }
+        }
+        self.line(&element.children[run_start..], depth + 1)?;
+        self.pad(depth)?;
+        write_end_tag(self.out, element)?;
+        writeln!(self.out)
+    }
+}
+
+impl<'a> Element<'a> {
+    /// Write this element as indented HTML, with `indent` spaces per level of
+    /// nesting.
+    ///
+    /// Block elements get a line of their own with their content indented
+    /// below; runs of text and inline elements are kept together on one line
+    /// with their whitespace collapsed. The content of `pre`, `textarea`
+    /// and raw text elements like `script` and `style` is written untouched.
+    pub fn write_pretty<W: Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        Pretty {
+            out: w,
+            indent,
+            options: &SerializeOptions::default(),
+        }
+        .element(self, 0)
+    }
+
+    /// Write this element as HTML, keeping its whitespace as it is.
+    pub fn write_html<W: Write>(&self, w: &mut W, options: &SerializeOptions) -> io::Result<()> {
+        write_compact_element(w, self, false, options)
+    }
+
+    /// This element as HTML written with `options`. See
+    /// [`Element::write_html`].
+    pub fn to_html_with(&self, options: &SerializeOptions) -> String {
+        let mut out = vec![];
+        self.write_html(&mut out, options)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("serialized HTML is UTF-8")
+    }
+
+    /// This element as HTML written with the default options, so that void
+    /// elements are written like `<br>`. See [`Element::write_html`].
+    pub fn to_html(&self) -> String {
+        self.to_html_with(&SerializeOptions::default())
+    }
+
+    /// This element as indented HTML, two spaces per level. See
+    /// [`Element::write_pretty`].
+    pub fn to_pretty_html(&self) -> String {
+        let mut out = vec![];
+        self.write_pretty(&mut out, 2)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("serialized HTML is UTF-8")
+    }
+}
+
+impl Node<'_> {
+    /// Write this node as HTML, keeping its whitespace as it is.
+    pub fn write_html<W: Write>(&self, w: &mut W, options: &SerializeOptions) -> io::Result<()> {
+        write_compact(w, self, false, options)
+    }
+
+    /// This node as HTML written with `options`. See [`Node::write_html`].
+    pub fn to_html_with(&self, options: &SerializeOptions) -> String {
+        let mut out = vec![];
+        self.write_html(&mut out, options)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("serialized HTML is UTF-8")
+    }
+
+    /// This node as HTML written with the default options. See
+    /// [`Node::write_html`].
+    pub fn to_html(&self) -> String {
+        self.to_html_with(&SerializeOptions::default())
+    }
+}
+
+impl Document<'_> {
+    /// Write the whole document as HTML, its doctype included, keeping its
+    /// whitespace as it is.
+    pub fn write_html<W: Write>(&self, w: &mut W, options: &SerializeOptions) -> io::Result<()> {
+        for node in &self.nodes {
+            node.write_html(w, options)?;
+        }
+        Ok(())
+    }
+
+    /// The document as HTML written with `options`. See
+    /// [`Document::write_html`].
+    pub fn to_html_with(&self, options: &SerializeOptions) -> String {
+        let mut out = vec![];
+        self.write_html(&mut out, options)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("serialized HTML is UTF-8")
+    }
+
+    /// The document as HTML written with the default options. Parsing the
+    /// result gives the same tree again.
+    pub fn to_html(&self) -> String {
+        self.to_html_with(&SerializeOptions::default())
+    }
+}
+
+/// Lets the serializer write straight into a [`fmt::Formatter`]. It only
+/// ever writes whole `str`s, so every buffer is valid UTF-8.
+struct FormatterWriter<'f, 'g>(&'f mut fmt::Formatter<'g>);
+
+impl Write for FormatterWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(s).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write with `write`, as HTML with the default options, into `f`.
+fn format_html(
+    f: &mut fmt::Formatter<'_>,
+    write: impl FnOnce(&mut FormatterWriter, &SerializeOptions) -> io::Result<()>,
+) -> fmt::Result {
+    write(&mut FormatterWriter(f), &SerializeOptions::default()).map_err(|_| fmt::Error)
+}
+
+/// The node as HTML, the same as [`Node::to_html`].
+impl Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_html(f, |w, options| self.write_html(w, options))
+    }
+}
+
+/// The element as HTML, the same as [`Element::to_html`].
+impl Display for Element<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_html(f, |w, options| self.write_html(w, options))
+    }
+}
+
+/// The document as HTML, the same as [`Document::to_html`].
+impl Display for Document<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_html(f, |w, options| self.write_html(w, options))
+    }
+}
+
+/// The attribute as HTML, like `class="note"`, or just its name when it
+/// has no value.
+impl Display for Attribute<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_html(f, |w, _| write_attribute(w, self))
+    }
+}
+
+impl OwnedElement {
+    /// Write this element as HTML, escaped the same way as a parsed
+    /// [`Element`].
+    pub fn write_html<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_owned_element(w, self)
+    }
+
+    /// This element as HTML. See [`OwnedElement::write_html`].
+    pub fn to_html(&self) -> String {
+        let mut out = vec![];
+        self.write_html(&mut out)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("serialized HTML is UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, tests::sexp};
+
+    #[test]
+    fn pretty_html() {
+        let html = "<html><body><div class=\"card\" hidden>
+            <h1>Title   <b>bold</b></h1><p>Some   text
+            here</p>a &amp; b & c<script>if (a < b) {}</script><pre>  keep
+  this </pre><ul><li>one</li><li>two<br>three</li></ul></div></body></html>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(root) = &nodes[0].kind else {
+            panic!("Expected html, got: {:?}", &nodes[0].kind);
+        };
+        let expected = "<html>
+  <body>
+    <div class=\"card\" hidden>
+      <h1>Title <b>bold</b></h1>
+      <p>Some text here</p>
+      a &amp; b &amp; c
+      <script>if (a < b) {}</script>
+      <pre>  keep
+  this </pre>
+      <ul>
+        <li>one</li>
+        <li>two<br>three</li>
+      </ul>
+    </div>
+  </body>
+</html>
+";
+        assert_eq!(root.to_pretty_html(), expected);
+    }
+
+    #[test]
+    fn write_pretty_matches_to_pretty_html() {
+        let html = "<section><h2 title='say \"hi\"'>Heading</h2><p>a<i>b</i></p></section>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(root) = &nodes[0].kind else {
+            panic!("Expected section, got: {:?}", &nodes[0].kind);
+        };
+        let mut out = Vec::new();
+        root.write_pretty(&mut out, 4).expect("writing to a Vec");
+        let written = String::from_utf8(out).expect("valid UTF-8");
+        assert_eq!(
+            written,
+            "<section>\n    <h2 title=\"say &quot;hi&quot;\">Heading</h2>\n    <p>a<i>b</i></p>\n</section>\n"
+        );
+        assert_eq!(written.replace("    ", "  "), root.to_pretty_html());
+    }
+
+    #[test]
+    fn self_closing_styles() {
+        let html = "<p>a<br>b<br/>c<br />d<img src=\"x.png\"/></p>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(p) = &nodes[0].kind else {
+            panic!("Expected p, got: {:?}", &nodes[0].kind);
+        };
+        for (self_closing_style, expected) in [
+            (
+                SelfClosingStyle::Html,
+                "<p>a<br>b<br>c<br>d<img src=\"x.png\"></p>",
+            ),
+            (
+                SelfClosingStyle::Xhtml,
+                "<p>a<br />b<br />c<br />d<img src=\"x.png\" /></p>",
+            ),
+            (
+                SelfClosingStyle::Preserve,
+                "<p>a<br>b<br/>c<br />d<img src=\"x.png\"/></p>",
+            ),
+        ] {
+            let options = SerializeOptions {
+                self_closing_style,
+                ..Default::default()
+            };
+            assert_eq!(p.to_html_with(&options), expected);
+        }
+    }
+
+    #[test]
+    fn comment_policies() {
+        let html = "<div><!-- TODO: ask Sam --><p>a<!--b-->c</p></div>";
+        let nodes = Parser::new(html).parse();
+        let NodeKind::Element(div) = &nodes[0].kind else {
+            panic!("Expected div, got: {:?}", &nodes[0].kind);
+        };
+        for (comment_policy, expected) in [
+            (CommentPolicy::Keep, html),
+            (CommentPolicy::Strip, "<div><p>ac</p></div>"),
+            (
+                CommentPolicy::Redact,
+                "<div><!-- [redacted] --><p>a<!-- [redacted] -->c</p></div>",
+            ),
+        ] {
+            let options = SerializeOptions {
+                comment_policy,
+                ..Default::default()
+            };
+            assert_eq!(div.to_html_with(&options), expected);
+        }
+    }
+
+    #[test]
+    fn processing_instructions_round_trip() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<?xml-stylesheet type=\"text/xsl\" href=\"s.xsl\"?>\n<rss version=\"2.0\"><?page  break ?></rss>";
+        let nodes = Parser::new(xml).parse();
+        let options = SerializeOptions::default();
+        let written = nodes
+            .iter()
+            .map(|node| node.to_html_with(&options))
+            .collect::<String>();
+        assert_eq!(written, xml);
+        assert_eq!(
+            sexp(&nodes[0]),
+            "#pi(xml version=\"1.0\" encoding=\"UTF-8\")"
+        );
+    }
+
+    #[test]
+    fn display_as_html() {
+        let html = "<!doctype html><DIV Class=a title='say \"hi\"'>1 < 2<Br><span></span></DIV>";
+        let document = Parser::new(html).parse_document();
+        let NodeKind::Element(div) = &document.nodes()[1].kind else {
+            panic!("Expected div, got: {:?}", &document.nodes()[1].kind);
+        };
+        let expected =
+            "<DIV Class=\"a\" title=\"say &quot;hi&quot;\">1 &lt; 2<Br><span></span></DIV>";
+        assert_eq!(div.to_string(), expected);
+        assert_eq!(format!("{}", document.nodes()[1]), expected);
+        assert_eq!(format!("{document}"), format!("<!doctype html>{expected}"));
+        assert_eq!(
+            div.attributes()[1].to_string(),
+            "title=\"say &quot;hi&quot;\""
+        );
+    }
+
+    #[test]
+    fn display_deeply_nested() {
+        let depth = 100_000;
+        let html = "<div>".repeat(depth) + "<pre> x </pre>" + &"</div>".repeat(depth);
+        let document = Parser::new(&html).parse_document();
+        assert_eq!(document.to_string(), html);
+    }
+
+    #[test]
+    fn document_round_trip() {
+        let html = "<!DOCTYPE html>
+<html lang=en><head><title>A &amp; B</title>
+<style>p > a { color: red }</style></head>
+<body class='main page' data-x=1 hidden>
+<!-- nav --><p>1 < 2 & 3 > 2<br/><img src=a.png alt='say \"hi\"'></p>
+<ul><li>one<li>two</ul><svg><circle r=1 /></svg>
+<script>if (a < b && c) {}</script></body></html>";
+        let document = Parser::new(html).parse_document();
+        let written = document.to_html();
+        assert!(written.starts_with("<!DOCTYPE html>\n<html lang=\"en\">"));
+        assert!(written.contains(
+            "<p>1 &lt; 2 &amp; 3 &gt; 2<br><img src=\"a.png\" alt=\"say &quot;hi&quot;\"></p>"
+        ));
+        assert!(written.contains("<body class=\"main page\" data-x=\"1\" hidden>"));
+        assert!(written.contains("<script>if (a < b && c) {}</script>"));
+
+        // text is kept as written, so once escaped it stays the same
+        let reparsed = Parser::new(&written).parse_document();
+        let rewritten = reparsed.to_html();
+        assert_eq!(rewritten, written);
+        assert_eq!(Parser::new(&rewritten).parse(), reparsed.nodes());
+        assert_eq!(reparsed.nodes().len(), document.nodes().len());
+
+        let NodeKind::Element(html_element) = &document.nodes()[2].kind else {
+            panic!("Expected html, got: {:?}", &document.nodes()[2].kind);
+        };
+        let br = html_element.query_selector("br").unwrap();
+        assert_eq!(br.to_html(), "<br>");
+        let options = SerializeOptions {
+            self_closing_style: SelfClosingStyle::Xhtml,
+            ..Default::default()
+        };
+        assert_eq!(br.to_html_with(&options), "<br />");
+    }
+}
@@ -0,0 +1,102 @@
+//! A pass over the source that finds tags without building a tree.
+
+use std::ops::Range;
+
+use crate::tokenizer::{TokenKind, Tokenizer, TokenizerOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    Open,
+    Close,
+    /// A start tag ending with `/>`.
+    SelfClosing,
+    Comment,
+    /// A doctype, CDATA section or processing instruction.
+    Declaration,
+}
+
+/// Where a tag is in the source and what kind it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagRange {
+    pub kind: TagKind,
+    /// The byte range of the whole tag, from its `<` to its `>`.
+    pub range: Range<usize>,
+}
+
+/// Find every tag in `source`, in order, for tools like minifiers that rewrite
+/// the source in place and don't need a tree. Everything between the ranges
+/// is text.
+///
+/// The content of raw text elements like `<script>`, `<style>` and
+/// `<textarea>` is text, so tags written in it are not reported. A start tag
+/// cut off by the end of the input is left out.
+pub fn tag_ranges(source: &str) -> Vec<TagRange> {
+    let mut tokenizer = Tokenizer::with_options(source, TokenizerOptions::default());
+    let mut ranges = vec![];
+    let mut open_tag = None;
+    loop {
+        let start = tokenizer.here().byte_range().start;
+        let Some(token) = tokenizer.next() else {
+            break;
+        };
+        let end = tokenizer.here().byte_range().start;
+        let (kind, start) = match token.kind() {
+            TokenKind::TagName { .. } => {
+                open_tag = Some(start);
+                continue;
+            }
+            TokenKind::OpeningTagEnd | TokenKind::SelfClosingTagEnd => {
+                let Some(start) = open_tag.take() else {
+                    continue;
+                };
+                if token.kind() == &TokenKind::SelfClosingTagEnd {
+                    (TagKind::SelfClosing, start)
+                } else {
+                    (TagKind::Open, start)
+                }
+            }
+            TokenKind::TagEnd { .. } => (TagKind::Close, start),
+            TokenKind::Comment { .. } => (TagKind::Comment, start),
+            TokenKind::Doctype { .. }
+            | TokenKind::CData { .. }
+            | TokenKind::ProcessingInstruction { .. } => (TagKind::Declaration, start),
+            _ => continue,
+        };
+        ranges.push(TagRange {
+            kind,
+            range: start..end,
+        });
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_of_small_document() {
+        let html = "<!DOCTYPE html>\n<html>\n  <body class=\"x\">\n    <!-- nav -->\n    <br/><p>a < b</p>\n    <script>if (a<b) {}</script>\n  </body>\n</html>";
+        let ranges = tag_ranges(html);
+        assert_eq!(
+            ranges
+                .iter()
+                .map(|r| (r.kind, &html[r.range.clone()]))
+                .collect::<Vec<_>>(),
+            vec![
+                (TagKind::Declaration, "<!DOCTYPE html>"),
+                (TagKind::Open, "<html>"),
+                (TagKind::Open, "<body class=\"x\">"),
+                (TagKind::Comment, "<!-- nav -->"),
+                (TagKind::SelfClosing, "<br/>"),
+                (TagKind::Open, "<p>"),
+                (TagKind::Close, "</p>"),
+                (TagKind::Open, "<script>"),
+                (TagKind::Close, "</script>"),
+                (TagKind::Close, "</body>"),
+                (TagKind::Close, "</html>"),
+            ]
+        );
+        assert_eq!(ranges[1].range, 16..22);
+    }
+}
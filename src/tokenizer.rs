@@ -1,35 +1,86 @@
-use std::str::CharIndices;
+use std::{cell::Cell, str::CharIndices};
+
+use crate::is_raw_text_element;
 
 pub struct Tokenizer<'a> {
     source: &'a str,
+    options: TokenizerOptions,
 
     it: CharIndices<'a>,
     consume_mode: ConsumeMode,
 
-    line: usize,
-    column: usize,
+    position: Position,
+    /// Whether the last consumed character was a `\r`, so that a following
+    /// `\n` doesn't start another line.
+    after_carriage_return: bool,
+    /// Whether `<![CDATA[` starts a CDATA section, which it only does in
+    /// foreign content.
+    cdata_allowed: bool,
+    /// The name of the raw text element whose start tag is being consumed,
+    /// so that its content is consumed as text once the tag ends.
+    raw_text_element: Option<&'a str>,
+    /// The byte offset at which no more tokens are started, as if the input
+    /// ended there. See [`Tokenizer::stop_at`].
+    end: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenizerOptions {
+    /// Recognise unquoted attribute values written as template expressions,
+    /// `{...}` or `{{...}}`. The value runs up to the brace balancing the
+    /// opening one and keeps its braces.
+    pub template_expressions: bool,
+    /// Emit text consisting only of whitespace, such as the indentation
+    /// between tags, as [`TokenKind::Whitespace`] instead of
+    /// [`TokenKind::Text`].
+    pub whitespace_tokens: bool,
 }
 
 impl<'a> Tokenizer<'a> {
+    #[cfg(test)]
     pub fn new(source: &'a str) -> Self {
+        Self::with_options(source, TokenizerOptions::default())
+    }
+
+    pub fn with_options(source: &'a str, options: TokenizerOptions) -> Self {
         let it = source.char_indices();
         Self {
             source,
-            line: 0,
-            column: 0,
+            options,
+            position: Position { line: 0, column: 0 },
+            after_carriage_return: false,
+            cdata_allowed: false,
+            raw_text_element: None,
             consume_mode: ConsumeMode::OutsideTag,
+            end: source.len(),
             it,
         }
     }
 
     pub fn next(&mut self) -> Option<Token<'a>> {
+        if matches!(self.consume_mode, ConsumeMode::OutsideTag) && self.it.offset() >= self.end {
+            return None;
+        }
         match &mut self.consume_mode {
             ConsumeMode::OutsideTag => {
                 if let Some(comment) = self.consume_comment() {
                     Some(comment)
+                } else if let Some(doctype) = self.consume_doctype() {
+                    Some(doctype)
+                } else if let Some(cdata) = self.consume_cdata() {
+                    Some(cdata)
+                } else if let Some(comment) = self.consume_bogus_comment() {
+                    Some(comment)
+                } else if let Some(instruction) = self.consume_processing_instruction() {
+                    Some(instruction)
                 } else if let Some(tag) = self.consume_tag() {
-                    if !matches!(tag.kind, TokenKind::TagEnd { name: _ }) {
+                    self.raw_text_element = None;
+                    if let TokenKind::TagName { name } = tag.kind {
                         self.consume_mode = ConsumeMode::AttributeName;
+                        // foreign content has no raw text elements
+                        if !self.cdata_allowed && is_raw_text_element(name) {
+                            self.raw_text_element = Some(name);
+                        }
                     }
                     Some(tag)
                 } else {
@@ -38,12 +89,32 @@ impl<'a> Tokenizer<'a> {
             }
             ConsumeMode::AttributeName => {
                 self.consume_whitespace();
+                // a `/` not followed by `>`, as in `<a / href=x>`, is ignored
+                while self.look_ahead1().is_some_and(|(_, c)| c == '/')
+                    && !self.source[self.it.offset()..].starts_with("/>")
+                {
+                    self.consume_character('/');
+                    self.consume_whitespace();
+                }
                 if let Some(tag_end) = self.consume_opening_tag_end() {
-                    self.consume_mode = ConsumeMode::OutsideTag;
+                    self.consume_mode = match tag_end.kind {
+                        TokenKind::OpeningTagEnd if self.raw_text_element.is_some() => {
+                            ConsumeMode::RawText
+                        }
+                        _ => ConsumeMode::OutsideTag,
+                    };
                     Some(tag_end)
                 } else if let Some(attribute_name) = self.consume_attribute_name() {
                     self.consume_mode = ConsumeMode::AttributeValue;
                     Some(attribute_name)
+                } else if self.look_ahead1().is_some_and(|(_, c)| c == '=') {
+                    // a value without a name, like `<div ="value">`: give it an
+                    // empty name so that the value is still consumed
+                    self.consume_mode = ConsumeMode::AttributeValue;
+                    Some(Token {
+                        span: self.here(),
+                        kind: TokenKind::AttributeName { name: "" },
+                    })
                 } else {
                     None
                 }
@@ -52,12 +123,64 @@ impl<'a> Tokenizer<'a> {
                 self.consume_mode = ConsumeMode::AttributeName;
                 Some(self.consume_attribute_value())
             }
+            ConsumeMode::RawText => {
+                self.consume_mode = ConsumeMode::OutsideTag;
+                let name = self.raw_text_element.take().unwrap_or_default();
+                // empty content leaves nothing to emit before the end tag
+                self.consume_raw_text(name).or_else(|| self.next())
+            }
+        }
+    }
+
+    /// Set whether `<![CDATA[` starts a CDATA section, as it does in foreign
+    /// content, or a bogus comment, as it does in HTML. Elements in foreign
+    /// content never have raw text.
+    pub fn allow_cdata(&mut self, allowed: bool) {
+        self.cdata_allowed = allowed;
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Move to the byte offset `offset` without producing tokens, keeping
+    /// track of line and column.
+    pub fn skip_to(&mut self, offset: usize) {
+        self.consume_until(offset);
+    }
+
+    /// Produce no more tokens once the byte offset `offset` is reached between
+    /// two tokens, as if the input ended there. Unlike cutting the source off
+    /// there, a tag or comment that starts before `offset` and runs past it is
+    /// still consumed whole, so whether the tokens stopped exactly at `offset`
+    /// tells whether the source could be split there.
+    pub fn stop_at(&mut self, offset: usize) {
+        self.end = offset;
+    }
+
+    /// An empty span at the current position.
+    pub fn here(&self) -> Span<'a> {
+        Span::point(self.current_position(), self.it.offset())
+    }
+
+    /// The span covering everything from the start of `start` to the end of `end`.
+    pub fn join(&self, start: &Span<'a>, end: &Span<'a>) -> Span<'a> {
+        Span {
+            range: Range {
+                start: start.range.start.clone(),
+                end: end.range.end.clone(),
+            },
+            source: &self.source[start.start_offset..end.end_offset],
+            start_offset: start.start_offset,
+            end_offset: end.end_offset,
         }
     }
 
     fn consume_attribute_value(&mut self) -> Token<'a> {
+        self.consume_whitespace();
         self.consume_character('=')
             .map(|_| {
+                self.consume_whitespace();
                 if let Some(q) = self
                     .consume_character('"')
                     .or_else(|| self.consume_character('\''))
@@ -78,7 +201,7 @@ impl<'a> Tokenizer<'a> {
                         })
                         .unwrap_or_else(|| {
                             self.consume_character(q);
-                            let span = Span::point(self.current_position());
+                            let span = self.here();
                             let value = span.source;
                             Token {
                                 span,
@@ -86,26 +209,34 @@ impl<'a> Tokenizer<'a> {
                             }
                         })
                 } else {
-                    self.consume_characters(|c| !c.is_whitespace() && c != &'>' && c != &'/')
-                        .map(|span| {
-                            let value = span.source;
-                            Token {
-                                span,
-                                kind: TokenKind::AttributeValue { value },
-                            }
-                        })
-                        .unwrap_or_else(|| {
-                            let span = Span::point(self.current_position());
-                            let value = span.source;
-                            Token {
-                                span,
-                                kind: TokenKind::AttributeValue { value },
-                            }
-                        })
+                    let is_expression = self.options.template_expressions
+                        && self.look_ahead1().is_some_and(|(_, c)| c == '{');
+                    if is_expression {
+                        self.consume_expression()
+                    } else {
+                        // a `/` is part of an unquoted value, so `<a href=/>`
+                        // links to `/` rather than closing the tag
+                        self.consume_characters(|c| !is_html_whitespace(*c) && c != &'>')
+                    }
+                    .map(|span| {
+                        let value = span.source;
+                        Token {
+                            span,
+                            kind: TokenKind::AttributeValue { value },
+                        }
+                    })
+                    .unwrap_or_else(|| {
+                        let span = self.here();
+                        let value = span.source;
+                        Token {
+                            span,
+                            kind: TokenKind::AttributeValue { value },
+                        }
+                    })
                 }
             })
             .unwrap_or_else(|| {
-                let span = Span::point(self.current_position());
+                let span = self.here();
                 let value = span.source;
                 Token {
                     span,
@@ -114,15 +245,96 @@ impl<'a> Tokenizer<'a> {
             })
     }
 
+    /// Consume a `{...}` expression up to and including the brace that
+    /// balances the opening one, or to the end of input if there is none.
+    fn consume_expression(&mut self) -> Option<Span<'a>> {
+        let depth = Cell::new(0usize);
+        let closed = Cell::new(false);
+        self.consume_characters(|c| {
+            if closed.get() {
+                return false;
+            }
+            match c {
+                '{' => depth.set(depth.get() + 1),
+                '}' => {
+                    depth.set(depth.get().saturating_sub(1));
+                    closed.set(depth.get() == 0);
+                }
+                _ => {}
+            }
+            true
+        })
+    }
+
     fn consume_opening_tag_end(&mut self) -> Option<Token<'a>> {
+        if let Some(span) = self.consume_string("/>") {
+            return Some(Token {
+                span,
+                kind: TokenKind::SelfClosingTagEnd,
+            });
+        }
         self.consume_character('>').map(|span| Token {
             span,
             kind: TokenKind::OpeningTagEnd,
         })
     }
 
+    /// Consume text up to the next `<` that starts markup. A `<` that doesn't
+    /// (as in `a < b`) is part of the text.
     fn consume_text_node(&mut self) -> Option<Token<'a>> {
-        self.consume_characters(|c| c != &'<').map(|text_span| {
+        let start = self.it.offset();
+        let rest = &self.source[start..];
+        let end = rest
+            .char_indices()
+            .skip(1)
+            .find(|(i, _)| starts_markup(&rest[*i..]))
+            .map_or(self.source.len(), |(i, _)| start + i);
+        let is_whitespace = self.options.whitespace_tokens
+            && self.source[start..end].chars().all(is_html_whitespace);
+        if is_whitespace {
+            return self.consume_until(end).map(|span| Token {
+                kind: TokenKind::Whitespace { text: span.source },
+                span,
+            });
+        }
+        self.consume_text_until(end)
+    }
+
+    /// Consume the content of a raw text element such as `<script>`, in which
+    /// nothing but its own end tag is markup, up to `</name` or the end of
+    /// input.
+    fn consume_raw_text(&mut self, name: &str) -> Option<Token<'a>> {
+        let start = self.it.offset();
+        let rest = &self.source[start..];
+        let end = rest
+            .match_indices("</")
+            .find(|(i, _)| {
+                let after = &rest[i + 2..];
+                after
+                    .get(..name.len())
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+                    && after[name.len()..]
+                        .chars()
+                        .next()
+                        .is_none_or(|c| is_html_whitespace(c) || c == '/' || c == '>')
+            })
+            .map_or(self.source.len(), |(i, _)| start + i);
+        self.consume_text_until(end)
+    }
+
+    /// Consume everything up to the byte offset `end`.
+    fn consume_until(&mut self, end: usize) -> Option<Span<'a>> {
+        let offset = Cell::new(self.it.offset());
+        self.consume_characters(|c| {
+            let at = offset.get();
+            offset.set(at + c.len_utf8());
+            at < end
+        })
+    }
+
+    /// Consume everything up to the byte offset `end` as a text token.
+    fn consume_text_until(&mut self, end: usize) -> Option<Token<'a>> {
+        self.consume_until(end).map(|text_span| {
             let text = text_span.source;
             Token {
                 span: text_span,
@@ -131,30 +343,155 @@ impl<'a> Tokenizer<'a> {
         })
     }
 
+    /// Consume a `<!--...-->` comment, or everything to the end of input if it
+    /// isn't terminated. The token's span covers only the text inside.
     fn consume_comment(&mut self) -> Option<Token<'a>> {
-        self.consume_string("<!--").and_then(|_| {
-            self.consume_till_string("-->").map(|span| {
-                self.move_cursor(3); // consume the last 3
-                let comment = span.source;
-                Token {
-                    span,
-                    kind: TokenKind::Comment { comment },
-                }
-            })
+        let source = self.source;
+        let start = self.it.offset();
+        let rest = source[start..].strip_prefix("<!--")?;
+        let end = rest.find("-->").unwrap_or(rest.len());
+        self.move_cursor(4);
+        let span = self
+            .consume_until(start + 4 + end)
+            .unwrap_or_else(|| self.here());
+        self.consume_string("-->");
+        Some(Token {
+            kind: TokenKind::Comment { text: span.source },
+            span,
+        })
+    }
+
+    /// Consume a `<![CDATA[...]]>` section, or everything to the end of input
+    /// if it isn't terminated. The token's span covers only the text inside.
+    ///
+    /// Where CDATA isn't allowed the same markup is a bogus comment instead,
+    /// holding everything between `<!` and the next `>`.
+    fn consume_cdata(&mut self) -> Option<Token<'a>> {
+        let source = self.source;
+        let start = self.it.offset();
+        let rest = &source[start..];
+        if !rest.starts_with("<![CDATA[") {
+            return None;
+        }
+        if !self.cdata_allowed {
+            return self.consume_bogus_comment();
+        }
+        self.move_cursor(9);
+        let end = rest.find("]]>").unwrap_or(rest.len());
+        let span = self
+            .consume_until(start + end)
+            .unwrap_or_else(|| self.here());
+        self.consume_string("]]>");
+        let text = span.source;
+        Some(Token {
+            span,
+            kind: TokenKind::CData { text },
+        })
+    }
+
+    /// Consume markup starting with `<!` that isn't a comment, doctype or
+    /// CDATA section, like `<!b>`, as a comment holding everything between
+    /// `<!` and the next `>`, or the end of input if there is none.
+    fn consume_bogus_comment(&mut self) -> Option<Token<'a>> {
+        let start = self.it.offset();
+        let rest = self.source[start..].strip_prefix("<!")?;
+        let end = rest.find('>').unwrap_or(rest.len());
+        self.move_cursor(2);
+        let span = self
+            .consume_until(start + 2 + end)
+            .unwrap_or_else(|| self.here());
+        self.consume_character('>');
+        Some(Token {
+            kind: TokenKind::Comment { text: span.source },
+            span,
+        })
+    }
+
+    /// Consume a `<?target data?>` processing instruction, or everything to
+    /// the end of input if it isn't terminated. The token's span covers
+    /// everything between `<?` and `?>`, so that it can be written back
+    /// exactly.
+    fn consume_processing_instruction(&mut self) -> Option<Token<'a>> {
+        let source = self.source;
+        let start = self.it.offset();
+        let after = source[start..].strip_prefix("<?")?;
+        let inner = &after[..after.find("?>").unwrap_or(after.len())];
+        let target_end = inner.find(is_html_whitespace).unwrap_or(inner.len());
+        let target = &inner[..target_end];
+        let data = inner[target_end..].trim_start_matches(is_html_whitespace);
+        self.move_cursor(2);
+        let span = self
+            .consume_until(start + 2 + inner.len())
+            .unwrap_or_else(|| self.here());
+        self.consume_string("?>");
+        Some(Token {
+            span,
+            kind: TokenKind::ProcessingInstruction { target, data },
+        })
+    }
+
+    /// Consume a `<!DOCTYPE name>` declaration up to its `>`, or to the end of
+    /// input if it has none. The keywords are matched ignoring ASCII case.
+    ///
+    /// Legacy doctypes name a DTD after the root element, as
+    /// `PUBLIC "public id" "system id"` or `SYSTEM "system id"`; the ids are
+    /// taken without their quotes.
+    fn consume_doctype(&mut self) -> Option<Token<'a>> {
+        let source = self.source;
+        let start = self.it.offset();
+        let rest = &source[start..];
+        if !rest
+            .get(..9)
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("<!doctype"))
+        {
+            return None;
+        }
+        let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+        let inner = rest[9..end]
+            .trim_end_matches('>')
+            .trim_start_matches(is_html_whitespace);
+        let name_end = inner.find(is_html_whitespace).unwrap_or(inner.len());
+        let name = &inner[..name_end];
+        let after_name = inner[name_end..].trim_start_matches(is_html_whitespace);
+        let keyword_end = after_name
+            .find(|c| is_html_whitespace(c) || c == '"' || c == '\'')
+            .unwrap_or(after_name.len());
+        let ids = &after_name[keyword_end..];
+        let (public_id, system_id) = match &after_name[..keyword_end] {
+            keyword if keyword.eq_ignore_ascii_case("public") => match quoted(ids) {
+                Some((public_id, rest)) => (Some(public_id), quoted(rest).map(|(id, _)| id)),
+                None => (None, None),
+            },
+            keyword if keyword.eq_ignore_ascii_case("system") => {
+                (None, quoted(ids).map(|(id, _)| id))
+            }
+            _ => (None, None),
+        };
+        self.consume_until(start + end).map(|span| Token {
+            span,
+            kind: TokenKind::Doctype {
+                name,
+                public_id,
+                system_id,
+            },
         })
     }
 
     fn consume_tag(&mut self) -> Option<Token<'a>> {
         let mut it_clone = self.it.clone();
-        if let Some((_i, c)) = it_clone.next() {
-            if c == '<' {
+        if let Some((i, c)) = it_clone.next() {
+            if c == '<' && starts_markup(&self.source[i..]) {
                 // it's a tag, let's start consumption
                 self.move_cursor(1);
                 let is_closing = self.consume_character('/').is_some();
-                let identifier = self
-                    .consume_tag_name()
-                    .unwrap_or_else(|| Span::point(self.current_position()));
+                let identifier = self.consume_tag_name().unwrap_or_else(|| self.here());
                 if is_closing {
+                    // anything else in an end tag, like whitespace or
+                    // attributes, is ignored up to its `>`
+                    let rest = self.it.offset();
+                    if let Some(end) = self.source[rest..].find('>') {
+                        self.consume_until(rest + end);
+                    }
                     self.consume_character('>');
                 }
                 let name = identifier.source;
@@ -186,43 +523,20 @@ impl<'a> Tokenizer<'a> {
 
     fn consume_identifier(&mut self) -> Option<Span<'a>> {
         self.consume_whitespace();
-        self.consume_characters(|c| c != &'=' && c != &'>' && !c.is_whitespace())
+        self.consume_characters(|c| c != &'=' && c != &'/' && c != &'>' && !is_html_whitespace(*c))
     }
 
     fn consume_tag_name(&mut self) -> Option<Span<'a>> {
         self.consume_whitespace();
-        self.consume_characters(|c| c != &'=' && c != &'/' && c != &'>' && !c.is_whitespace())
+        self.consume_characters(|c| c != &'=' && c != &'/' && c != &'>' && !is_html_whitespace(*c))
     }
 
     fn current_position(&self) -> Position {
-        Position {
-            line: self.line,
-            column: self.column,
-        }
+        self.position.clone()
     }
 
-    fn consume_whitespace(&mut self) {
-        self.consume_characters(|c| c.is_whitespace());
-    }
-
-    fn consume_till_string(&mut self, s: &str) -> Option<Span<'a>> {
-        let mut it_clone = self.it.clone();
-        it_clone.next().and_then(|(i, c)| {
-            let rest = &self.source[i..];
-            println!("({},{})", i, c);
-            rest.find(s).map(|s_index| {
-                println!("s_index: {s_index}");
-                let move_by = s_index;
-                let start = self.current_position();
-                self.move_cursor(move_by);
-                let end = self.current_position();
-                let source = &self.source[i..i + s_index];
-                Span {
-                    range: Range { start, end },
-                    source,
-                }
-            })
-        })
+    pub fn consume_whitespace(&mut self) {
+        self.consume_characters(|c| is_html_whitespace(*c));
     }
 
     fn consume_string(&mut self, s: &str) -> Option<Span<'a>> {
@@ -232,7 +546,7 @@ impl<'a> Tokenizer<'a> {
                 None
             } else {
                 let end_index = i + s.len();
-                let slice = if self.source[i..].is_char_boundary(end_index) {
+                let slice = if self.source.is_char_boundary(end_index) {
                     &self.source[i..end_index]
                 } else {
                     ""
@@ -244,6 +558,8 @@ impl<'a> Tokenizer<'a> {
                     Some(Span {
                         range: Range { start, end },
                         source: &self.source[i..end_index],
+                        start_offset: i,
+                        end_offset: end_index,
                     })
                 } else {
                     None
@@ -258,17 +574,15 @@ impl<'a> Tokenizer<'a> {
             if next_c != c {
                 return None;
             }
-            let end = self.current_position();
-            if c == '\n' {
-                self.line += 1;
-                self.column = 0;
-            } else {
-                self.column += 1;
-            }
+            self.track_position(c);
             self.it.next();
+            let end = self.current_position();
+            let end_offset = i + c.len_utf8();
             Some(Span {
                 range: Range { start, end },
-                source: &self.source[i..i + 1],
+                source: &self.source[i..end_offset],
+                start_offset: i,
+                end_offset,
             })
         } else {
             None
@@ -280,10 +594,7 @@ impl<'a> Tokenizer<'a> {
     where
         F: Fn(&char) -> bool,
     {
-        let start = Position {
-            line: self.line,
-            column: self.column,
-        };
+        let start = self.current_position();
         let mut start_index = None;
         let mut last_index = 0;
         let mut last_index_len = 1;
@@ -296,37 +607,32 @@ impl<'a> Tokenizer<'a> {
             if start_index.is_none() {
                 start_index = Some(i);
             }
-            if c == '\n' {
-                self.line += 1;
-                self.column = 0;
-            } else {
-                self.column += 1;
-            }
+            self.track_position(c);
             last_index = i;
             last_index_len = c.len_utf8();
         }
-        let end = Position {
-            line: self.line,
-            column: self.column,
-        };
+        let end = self.current_position();
         start_index.map(|start_index| Span {
             range: Range { start, end },
             source: &self.source[start_index..last_index + last_index_len],
+            start_offset: start_index,
+            end_offset: last_index + last_index_len,
         })
     }
 
+    /// Update the line and column for a consumed character.
+    fn track_position(&mut self, c: char) {
+        self.position.advance(c, self.after_carriage_return);
+        self.after_carriage_return = c == '\r';
+    }
+
     fn move_cursor(&mut self, by: usize) {
         if by == 0 {
             return;
         }
         for _ in 0..by {
             if let Some((_, c)) = self.it.next() {
-                if c == '\n' {
-                    self.line += 1;
-                    self.column = 0;
-                } else {
-                    self.column += 1;
-                }
+                self.track_position(c);
             }
         }
     }
@@ -336,6 +642,34 @@ impl<'a> Tokenizer<'a> {
     }
 }
 
+/// Whether `c` is whitespace as HTML defines it: space, tab, line feed, form
+/// feed or carriage return. Other Unicode spaces, such as the no-break space,
+/// are ordinary characters in markup.
+pub fn is_html_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\x0C' | '\r')
+}
+
+/// Split a single- or double-quoted string, after any whitespace, off the
+/// start of `s`, returning its content and what follows it. A string without
+/// its closing quote runs to the end of `s`.
+fn quoted(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start_matches(is_html_whitespace);
+    let quote = s.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let content = &s[1..];
+    let end = content.find(quote).unwrap_or(content.len());
+    Some((&content[..end], content.get(end + 1..).unwrap_or("")))
+}
+
+/// Whether `s` starts with a `<` that opens a tag, end tag, comment or other
+/// markup declaration rather than being literal text.
+fn starts_markup(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next() == Some('<')
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'))
+}
+
 #[derive(Debug)]
 pub struct Token<'a> {
     span: Span<'a>,
@@ -349,41 +683,175 @@ impl<'a> Token<'a> {
     pub fn span(&self) -> &Span<'a> {
         &self.span
     }
+
+    /// This tag name token with its name replaced by `name`, keeping the span
+    /// of the name as written.
+    pub(crate) fn renamed(self, name: &'a str) -> Token<'a> {
+        match self.kind {
+            TokenKind::TagName { .. } => Token {
+                kind: TokenKind::TagName { name },
+                span: self.span,
+            },
+            _ => self,
+        }
+    }
+
+    /// This token in `source`, where its text has moved as `shift` says.
+    pub(crate) fn rebased<'b>(&self, source: &'b str, shift: &Shift) -> Token<'b> {
+        let span = self.span.rebased(source, shift);
+        let old = self.span.source;
+        let new = span.source;
+        // the strings in a kind are slices of the span's text, so they keep
+        // their place in it
+        let moved = |s: &str| -> &'b str {
+            if s.is_empty() {
+                return "";
+            }
+            let start = (s.as_ptr() as usize).wrapping_sub(old.as_ptr() as usize);
+            new.get(start..start + s.len()).unwrap_or("")
+        };
+        let kind = match self.kind {
+            // a name renamed while parsing isn't in the source, so it goes
+            // back to the name as written
+            TokenKind::TagName { name }
+                if !old.as_bytes().as_ptr_range().contains(&name.as_ptr()) =>
+            {
+                TokenKind::TagName { name: new }
+            }
+            TokenKind::TagName { name } => TokenKind::TagName { name: moved(name) },
+            TokenKind::OpeningTagEnd => TokenKind::OpeningTagEnd,
+            TokenKind::SelfClosingTagEnd => TokenKind::SelfClosingTagEnd,
+            TokenKind::AttributeName { name } => TokenKind::AttributeName { name: moved(name) },
+            TokenKind::AttributeValue { value } => TokenKind::AttributeValue {
+                value: moved(value),
+            },
+            TokenKind::Text { text } => TokenKind::Text { text: moved(text) },
+            TokenKind::Whitespace { text } => TokenKind::Whitespace { text: moved(text) },
+            TokenKind::TagEnd { name } => TokenKind::TagEnd { name: moved(name) },
+            TokenKind::Comment { text } => TokenKind::Comment { text: moved(text) },
+            TokenKind::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => TokenKind::Doctype {
+                name: moved(name),
+                public_id: public_id.map(moved),
+                system_id: system_id.map(moved),
+            },
+            TokenKind::CData { text } => TokenKind::CData { text: moved(text) },
+            TokenKind::ProcessingInstruction { target, data } => TokenKind::ProcessingInstruction {
+                target: moved(target),
+                data: moved(data),
+            },
+        };
+        Token { span, kind }
+    }
+}
+
+/// How text after an edit moved: by `offset_delta` bytes and `line_delta`
+/// lines, with columns on `line`, the line the edit ended on, also moving by
+/// `column_delta`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shift {
+    pub offset_delta: isize,
+    pub line: usize,
+    pub line_delta: isize,
+    pub column_delta: isize,
+}
+
+impl Shift {
+    pub(crate) fn offset(&self, offset: usize) -> usize {
+        offset.wrapping_add_signed(self.offset_delta)
+    }
+
+    fn position(&self, position: &Position) -> Position {
+        let column = if position.line == self.line {
+            position.column.wrapping_add_signed(self.column_delta)
+        } else {
+            position.column
+        };
+        Position {
+            line: position.line.wrapping_add_signed(self.line_delta),
+            column,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum TokenKind<'a> {
-    TagName { name: &'a str },
+    TagName {
+        name: &'a str,
+    },
     OpeningTagEnd,
-    AttributeName { name: &'a str },
-    AttributeValue { value: &'a str },
-    Text { text: &'a str },
-    TagEnd { name: &'a str },
-    Comment { comment: &'a str },
+    /// The `/>` ending a start tag like `<br/>`.
+    SelfClosingTagEnd,
+    AttributeName {
+        name: &'a str,
+    },
+    AttributeValue {
+        value: &'a str,
+    },
+    Text {
+        text: &'a str,
+    },
+    Whitespace {
+        text: &'a str,
+    },
+    TagEnd {
+        name: &'a str,
+    },
+    Comment {
+        text: &'a str,
+    },
+    Doctype {
+        name: &'a str,
+        public_id: Option<&'a str>,
+        system_id: Option<&'a str>,
+    },
+    CData {
+        text: &'a str,
+    },
+    ProcessingInstruction {
+        target: &'a str,
+        data: &'a str,
+    },
 }
 
 enum ConsumeMode {
     AttributeName,
     AttributeValue,
     OutsideTag,
+    /// The content of a raw text element, right after its start tag.
+    RawText,
 }
 
+/// A part of the source, with where it starts and ends as byte offsets and as
+/// lines and columns.
 #[derive(Clone, Debug)]
 pub struct Span<'a> {
     range: Range,
     source: &'a str,
+    start_offset: usize,
+    end_offset: usize,
 }
 impl<'a> Span<'a> {
-    fn point(pos: Position) -> Self {
+    fn point(pos: Position, offset: usize) -> Self {
         Self {
             range: Range {
                 start: pos.clone(),
                 end: pos,
             },
             source: "",
+            start_offset: offset,
+            end_offset: offset,
         }
     }
 
+    /// An empty span where this one ends.
+    pub fn end_point(&self) -> Self {
+        Span::point(self.range.end.clone(), self.end_offset)
+    }
+
     pub fn source(&self) -> &'a str {
         self.source
     }
@@ -391,6 +859,69 @@ impl<'a> Span<'a> {
     pub fn range(&self) -> &Range {
         &self.range
     }
+
+    /// The line and column where this span starts.
+    ///
+    /// Both are 0-based, as in editor protocols like LSP: the first character
+    /// of the input is at `(0, 0)`. Columns count characters, not bytes, and
+    /// `\n`, `\r` and `\r\n` each end a line.
+    pub fn start(&self) -> (usize, usize) {
+        (self.range.start.line, self.range.start.column)
+    }
+
+    /// The 0-based line and column just past the end of this span. See
+    /// [`Span::start`].
+    pub fn end(&self) -> (usize, usize) {
+        (self.range.end.line, self.range.end.column)
+    }
+
+    /// The part of this span covering `range`, a byte range relative to the
+    /// start of the span, with its byte offsets, lines and columns worked out
+    /// from those of this span.
+    ///
+    /// # Panics
+    ///
+    /// If `range` isn't within the span or doesn't fall on character
+    /// boundaries, like slicing a `str`.
+    pub fn subspan(&self, range: std::ops::Range<usize>) -> Span<'a> {
+        let mut start = self.range.start.clone();
+        let mut after_carriage_return = false;
+        for c in self.source[..range.start].chars() {
+            start.advance(c, after_carriage_return);
+            after_carriage_return = c == '\r';
+        }
+        let mut end = start.clone();
+        for c in self.source[range.clone()].chars() {
+            end.advance(c, after_carriage_return);
+            after_carriage_return = c == '\r';
+        }
+        Span {
+            range: Range { start, end },
+            source: &self.source[range.clone()],
+            start_offset: self.start_offset + range.start,
+            end_offset: self.start_offset + range.end,
+        }
+    }
+
+    /// This span in `source`, where its text has moved as `shift` says.
+    pub(crate) fn rebased<'b>(&self, source: &'b str, shift: &Shift) -> Span<'b> {
+        let start_offset = shift.offset(self.start_offset);
+        let end_offset = shift.offset(self.end_offset);
+        Span {
+            range: Range {
+                start: shift.position(&self.range.start),
+                end: shift.position(&self.range.end),
+            },
+            source: &source[start_offset..end_offset],
+            start_offset,
+            end_offset,
+        }
+    }
+
+    /// The byte range of this span in the tokenized source.
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.start_offset..self.end_offset
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -399,12 +930,29 @@ pub struct Range {
     pub end: Position,
 }
 
+/// A place in the source as a line and a column, both 0-based. See
+/// [`Span::start`].
 #[derive(Clone, Debug)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
 }
 
+impl Position {
+    /// Move past `c`. `\n`, `\r` and `\r\n` each end a line, so a `\n`
+    /// right after a `\r` doesn't start another one.
+    fn advance(&mut self, c: char, after_carriage_return: bool) {
+        match c {
+            '\n' if after_carriage_return => {}
+            '\n' | '\r' => {
+                self.line += 1;
+                self.column = 0;
+            }
+            _ => self.column += 1,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -421,7 +969,7 @@ mod test {
             </body>
             </html>
             ";
-        let mut tokenizer = Tokenizer::new(&source);
+        let mut tokenizer = Tokenizer::new(source);
         let expected_kinds = vec![
             TokenKind::Text {
                 text: "\n            ",
@@ -464,7 +1012,7 @@ mod test {
         for (i, k) in expected_kinds.iter().enumerate() {
             let got_token = tokenizer
                 .next()
-                .expect(&format!("Token to exist. iteration: {i}"));
+                .unwrap_or_else(|| panic!("Token to exist. iteration: {i}"));
             assert_eq!((i, &got_token.kind), (i, k));
         }
         assert!(tokenizer.next().is_none());
@@ -473,7 +1021,7 @@ mod test {
     #[test]
     fn attrib_basic() {
         let s = "<tag-name attr-name=attr-value>";
-        let expected_kinds = vec![
+        let expected_kinds = [
             TokenKind::TagName { name: "tag-name" },
             TokenKind::AttributeName { name: "attr-name" },
             TokenKind::AttributeValue {
@@ -481,7 +1029,7 @@ mod test {
             },
             TokenKind::OpeningTagEnd,
         ];
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         for (i, k) in expected_kinds.iter().enumerate() {
             let got = tokenizer.next().expect("should exist");
             assert_eq!((i, &got.kind), (i, k));
@@ -497,7 +1045,7 @@ mod test {
             TokenKind::AttributeValue { value: "" },
             TokenKind::OpeningTagEnd,
         ];
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         for (i, k) in expected_kinds.into_iter().enumerate() {
             let got = tokenizer.next().map(|g| g.kind);
             assert_eq!((i, got), (i, Some(k)));
@@ -515,7 +1063,7 @@ mod test {
             },
             TokenKind::OpeningTagEnd,
         ];
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         for (i, k) in expected_kinds.into_iter().enumerate() {
             let got = tokenizer.next().map(|g| g.kind);
             assert_eq!((i, got), (i, Some(k)));
@@ -533,7 +1081,7 @@ mod test {
             TokenKind::AttributeValue { value: "attr-val" },
             TokenKind::OpeningTagEnd,
         ];
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         for (i, k) in expected_kinds.into_iter().enumerate() {
             let got = tokenizer.next().map(|g| g.kind);
             assert_eq!((i, got), (i, Some(k)));
@@ -543,7 +1091,7 @@ mod test {
     #[test]
     fn tag_name_with_dashes() {
         let s = "<custom-element>";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         let token = tokenizer.next().expect("should exist");
         assert_eq!(
             token.kind,
@@ -556,7 +1104,7 @@ mod test {
     #[test]
     fn empty_attribute_value_quoted() {
         let s = "<tag attr=\"\">";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         tokenizer.next(); // tag
         tokenizer.next(); // attr name
         let token = tokenizer.next().expect("should exist");
@@ -570,7 +1118,7 @@ mod test {
     #[test]
     fn attribute_value_with_mixed_quotes() {
         let s = "<tag attr=\" He said 'hello' \">";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         tokenizer.next(); // tag
         tokenizer.next(); // attr name
         let token = tokenizer.next().expect("should exist");
@@ -585,24 +1133,18 @@ mod test {
     #[test]
     fn self_closing_tag() {
         let s = "<tag/>"; // not valid html but still
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         let tag_name = tokenizer.next().expect("should exist");
         assert_eq!(tag_name.kind, TokenKind::TagName { name: "tag" });
-        let attribute_name = tokenizer.next().expect("should exist");
-        assert_eq!(attribute_name.kind, TokenKind::AttributeName { name: "/" });
-        let attribute_value = tokenizer.next().expect("should exist");
-        assert_eq!(
-            attribute_value.kind,
-            TokenKind::AttributeValue { value: "" }
-        );
         let tag_end = tokenizer.next().expect("should exist");
-        assert_eq!(tag_end.kind, TokenKind::OpeningTagEnd);
+        assert_eq!(tag_end.kind, TokenKind::SelfClosingTagEnd);
+        assert_eq!(tag_end.span.source, "/>");
     }
 
     #[test]
     fn self_closing_tag_with_attributes() {
-        let s = "<tag a b c=d/>"; // not valid html but still
-        let mut tokenizer = Tokenizer::new(&s);
+        let s = "<tag a b c=d />"; // not valid html but still
+        let mut tokenizer = Tokenizer::new(s);
         let tag_name = tokenizer.next().expect("should exist");
         assert_eq!(tag_name.kind, TokenKind::TagName { name: "tag" });
         let attrib_a = tokenizer.next().expect("should exist");
@@ -620,21 +1162,60 @@ mod test {
             attrib_value_c.kind,
             TokenKind::AttributeValue { value: "d" }
         );
-        let attribute_name = tokenizer.next().expect("should exist");
-        assert_eq!(attribute_name.kind, TokenKind::AttributeName { name: "/" });
-        let attribute_value = tokenizer.next().expect("should exist");
-        assert_eq!(
-            attribute_value.kind,
-            TokenKind::AttributeValue { value: "" }
-        );
         let tag_end = tokenizer.next().expect("should exist");
-        assert_eq!(tag_end.kind, TokenKind::OpeningTagEnd);
+        assert_eq!(tag_end.kind, TokenKind::SelfClosingTagEnd);
+    }
+
+    #[test]
+    fn slashes_that_dont_close_tags() {
+        for (s, expected_kinds) in [
+            (
+                "<tag / >",
+                vec![TokenKind::TagName { name: "tag" }, TokenKind::OpeningTagEnd],
+            ),
+            (
+                "<tag a/b>",
+                vec![
+                    TokenKind::TagName { name: "tag" },
+                    TokenKind::AttributeName { name: "a" },
+                    TokenKind::AttributeValue { value: "" },
+                    TokenKind::AttributeName { name: "b" },
+                    TokenKind::AttributeValue { value: "" },
+                    TokenKind::OpeningTagEnd,
+                ],
+            ),
+            (
+                "<tag attr=/>",
+                vec![
+                    TokenKind::TagName { name: "tag" },
+                    TokenKind::AttributeName { name: "attr" },
+                    TokenKind::AttributeValue { value: "/" },
+                    TokenKind::OpeningTagEnd,
+                ],
+            ),
+            (
+                "<tag disabled/>",
+                vec![
+                    TokenKind::TagName { name: "tag" },
+                    TokenKind::AttributeName { name: "disabled" },
+                    TokenKind::AttributeValue { value: "" },
+                    TokenKind::SelfClosingTagEnd,
+                ],
+            ),
+        ] {
+            let mut tokenizer = Tokenizer::new(s);
+            for (i, k) in expected_kinds.into_iter().enumerate() {
+                let got = tokenizer.next().map(|g| g.kind);
+                assert_eq!((s, i, got), (s, i, Some(k)));
+            }
+            assert!(tokenizer.next().is_none());
+        }
     }
 
     #[test]
     fn attribute_name_starts_with_number() {
         let s = "<tag 1attr=value>";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         tokenizer.next();
         let token = tokenizer.next().expect("should exist");
         assert_eq!(token.kind, TokenKind::AttributeName { name: "1attr" });
@@ -643,7 +1224,7 @@ mod test {
     #[test]
     fn attribute_with_no_value_and_then_another_attribute_with_value() {
         let s = "<tag attr1 attr2=value2>";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
 
         tokenizer.next(); // tag
         assert_eq!(
@@ -667,12 +1248,599 @@ mod test {
     #[test]
     fn comment() {
         let s = "<!-- hello this is a comment -->";
-        let mut tokenizer = Tokenizer::new(&s);
+        let mut tokenizer = Tokenizer::new(s);
         assert_eq!(
             tokenizer.next().unwrap().kind,
             TokenKind::Comment {
-                comment: " hello this is a comment "
+                text: " hello this is a comment "
             }
         );
     }
+
+    #[test]
+    fn attrib_template_expression() {
+        let s = "<input value={count} disabled={ {a: 1}.a > 0 }>";
+        let options = TokenizerOptions {
+            template_expressions: true,
+            ..Default::default()
+        };
+        let mut tokenizer = Tokenizer::with_options(s, options);
+        let expected_kinds = vec![
+            TokenKind::TagName { name: "input" },
+            TokenKind::AttributeName { name: "value" },
+            TokenKind::AttributeValue { value: "{count}" },
+            TokenKind::AttributeName { name: "disabled" },
+            TokenKind::AttributeValue {
+                value: "{ {a: 1}.a > 0 }",
+            },
+            TokenKind::OpeningTagEnd,
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+    }
+
+    #[test]
+    fn attrib_double_brace_expression() {
+        let s = "<div style={{ {a:1} }}>text";
+        let options = TokenizerOptions {
+            template_expressions: true,
+            ..Default::default()
+        };
+        let mut tokenizer = Tokenizer::with_options(s, options);
+        tokenizer.next(); // tag
+        tokenizer.next(); // attr name
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::AttributeValue {
+                value: "{{ {a:1} }}"
+            })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::OpeningTagEnd)
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text { text: "text" })
+        );
+    }
+
+    #[test]
+    fn attrib_braces_without_template_expressions() {
+        let s = "<div style={{ a }}>";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.next(); // tag
+        tokenizer.next(); // attr name
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::AttributeValue { value: "{{" })
+        );
+    }
+
+    #[test]
+    fn comment_with_double_hyphens() {
+        let s = "<!-- a -- b --><p>";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().unwrap().kind,
+            TokenKind::Comment { text: " a -- b " }
+        );
+        assert_eq!(
+            tokenizer.next().unwrap().kind,
+            TokenKind::TagName { name: "p" }
+        );
+    }
+
+    #[test]
+    fn comment_of_hyphens() {
+        let s = "<!-----><!---->text";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().unwrap().kind,
+            TokenKind::Comment { text: "-" }
+        );
+        assert_eq!(
+            tokenizer.next().unwrap().kind,
+            TokenKind::Comment { text: "" }
+        );
+        assert_eq!(
+            tokenizer.next().unwrap().kind,
+            TokenKind::Text { text: "text" }
+        );
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let s = "<p>\r\none\r\n</p>\rtwo\n<br>";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.next(); // p
+        tokenizer.next(); // >
+        let text = tokenizer.next().expect("should exist");
+        assert_eq!(
+            text.kind,
+            TokenKind::Text {
+                text: "\r\none\r\n"
+            }
+        );
+        assert_eq!(text.span.byte_range(), 3..10);
+        assert_eq!(
+            (text.span.range.start.line, text.span.range.end.line),
+            (0, 2)
+        );
+        assert_eq!(text.span.range.end.column, 0);
+        let end_tag = tokenizer.next().expect("should exist");
+        assert_eq!(end_tag.kind, TokenKind::TagEnd { name: "p" });
+        assert_eq!(end_tag.span.range.start.line, 2);
+        let text = tokenizer.next().expect("should exist");
+        assert_eq!(text.kind, TokenKind::Text { text: "\rtwo\n" });
+        assert_eq!(text.span.range.end.line, 4);
+        let br = tokenizer.next().expect("should exist");
+        assert_eq!(
+            (br.span.range.start.line, br.span.range.start.column),
+            (4, 1)
+        );
+    }
+
+    #[test]
+    fn bare_less_than_is_text() {
+        let s = "a < b <3 x<y>";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text { text: "a < b <3 x" })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::TagName { name: "y" })
+        );
+        let s = "<<p>";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text { text: "<" })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::TagName { name: "p" })
+        );
+    }
+
+    #[test]
+    fn attribute_value_without_name() {
+        let s = "<div =\"value\">text";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::TagName { name: "div" },
+            TokenKind::AttributeName { name: "" },
+            TokenKind::AttributeValue { value: "value" },
+            TokenKind::OpeningTagEnd,
+            TokenKind::Text { text: "text" },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn raw_text() {
+        let s = "<script>if (a <b) { x = '</p>' }</SCRIPT >after";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::TagName { name: "script" })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::OpeningTagEnd)
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text {
+                text: "if (a <b) { x = '</p>' }"
+            })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::TagEnd { name: "SCRIPT" })
+        );
+
+        let mut tokenizer = Tokenizer::new("a </scripts> b");
+        assert_eq!(
+            tokenizer.consume_raw_text("script").map(|t| t.kind),
+            Some(TokenKind::Text {
+                text: "a </scripts> b"
+            })
+        );
+        assert!(tokenizer.consume_raw_text("script").is_none());
+
+        fn kinds(mut tokenizer: Tokenizer) -> Vec<TokenKind> {
+            std::iter::from_fn(|| tokenizer.next().map(|t| t.kind)).collect()
+        }
+        assert_eq!(
+            kinds(Tokenizer::new("<title a=b><b></title><style></style>")),
+            vec![
+                TokenKind::TagName { name: "title" },
+                TokenKind::AttributeName { name: "a" },
+                TokenKind::AttributeValue { value: "b" },
+                TokenKind::OpeningTagEnd,
+                TokenKind::Text { text: "<b>" },
+                TokenKind::TagEnd { name: "title" },
+                TokenKind::TagName { name: "style" },
+                TokenKind::OpeningTagEnd,
+                TokenKind::TagEnd { name: "style" },
+            ]
+        );
+        // foreign content has no raw text
+        let mut tokenizer = Tokenizer::new("<title><b>");
+        tokenizer.allow_cdata(true);
+        assert_eq!(
+            kinds(tokenizer),
+            vec![
+                TokenKind::TagName { name: "title" },
+                TokenKind::OpeningTagEnd,
+                TokenKind::TagName { name: "b" },
+                TokenKind::OpeningTagEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_need_space_after_tag_name() {
+        let s = "<input type=text><inputtype=text>";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::TagName { name: "input" },
+            TokenKind::AttributeName { name: "type" },
+            TokenKind::AttributeValue { value: "text" },
+            TokenKind::OpeningTagEnd,
+            // the tag name runs up to the `=`, which then starts a value
+            // without a name
+            TokenKind::TagName { name: "inputtype" },
+            TokenKind::AttributeName { name: "" },
+            TokenKind::AttributeValue { value: "text" },
+            TokenKind::OpeningTagEnd,
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+
+        let mut tokenizer = Tokenizer::new("<divclass=\"x\">");
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::TagName { name: "divclass" })
+        );
+    }
+
+    #[test]
+    fn whitespace_around_equals() {
+        for s in [
+            "<a href =\"x\">",
+            "<a href = \"x\">",
+            "<a href=\n'x'>",
+            "<a href= x>",
+        ] {
+            let mut tokenizer = Tokenizer::new(s);
+            let expected_kinds = vec![
+                TokenKind::TagName { name: "a" },
+                TokenKind::AttributeName { name: "href" },
+                TokenKind::AttributeValue { value: "x" },
+                TokenKind::OpeningTagEnd,
+            ];
+            for (i, k) in expected_kinds.into_iter().enumerate() {
+                let got = tokenizer.next().map(|g| g.kind);
+                assert_eq!((i, got), (i, Some(k)), "{s}");
+            }
+            assert!(tokenizer.next().is_none());
+        }
+    }
+
+    #[test]
+    fn no_break_space_is_not_whitespace() {
+        let s = "<p\u{a0}title=\u{a0}x\u{a0} lang=\x0Cen>";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::TagName {
+                name: "p\u{a0}title",
+            },
+            TokenKind::AttributeName { name: "" },
+            TokenKind::AttributeValue {
+                value: "\u{a0}x\u{a0}",
+            },
+            TokenKind::AttributeName { name: "lang" },
+            TokenKind::AttributeValue { value: "en" },
+            TokenKind::OpeningTagEnd,
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn doctype() {
+        for (s, name, public_id, system_id) in [
+            ("<!DOCTYPE html><p>", "html", None, None),
+            ("<!doctype HTML ><p>", "HTML", None, None),
+            (
+                "<!DocType html PUBLIC \"-//W3C//DTD HTML 4.01//EN\"><p>",
+                "html",
+                Some("-//W3C//DTD HTML 4.01//EN"),
+                None,
+            ),
+            (
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\"\n  'http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd'><p>",
+                "html",
+                Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+                Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"),
+            ),
+            (
+                "<!DOCTYPE html system \"about:legacy-compat\"><p>",
+                "html",
+                None,
+                Some("about:legacy-compat"),
+            ),
+            (
+                "<!DOCTYPE svg PUBLIC\"-//W3C//DTD SVG 1.1//EN><p>",
+                "svg",
+                Some("-//W3C//DTD SVG 1.1//EN"),
+                None,
+            ),
+            ("<!DOCTYPE><p>", "", None, None),
+        ] {
+            let mut tokenizer = Tokenizer::new(s);
+            let doctype = tokenizer.next().expect("doctype");
+            assert_eq!(
+                doctype.kind,
+                TokenKind::Doctype {
+                    name,
+                    public_id,
+                    system_id
+                },
+                "{s}"
+            );
+            assert_eq!(doctype.span.source, &s[..s.len() - 3]);
+            assert_eq!(
+                tokenizer.next().map(|t| t.kind),
+                Some(TokenKind::TagName { name: "p" })
+            );
+        }
+    }
+
+    #[test]
+    fn unterminated_quoted_value() {
+        let s = "<a href=\"unterminated";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::TagName { name: "a" },
+            TokenKind::AttributeName { name: "href" },
+            TokenKind::AttributeValue {
+                value: "unterminated",
+            },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn cdata() {
+        let s = "<![CDATA[a]b]]c]]>x<![CDATA[]]><![CDATA[open";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.allow_cdata(true);
+        let expected_kinds = vec![
+            TokenKind::CData { text: "a]b]]c" },
+            TokenKind::Text { text: "x" },
+            TokenKind::CData { text: "" },
+            TokenKind::CData { text: "open" },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+
+        // in HTML the section ends at the first `>`
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::Comment {
+                text: "[CDATA[a]b]]c]]",
+            },
+            TokenKind::Text { text: "x" },
+            TokenKind::Comment { text: "[CDATA[]]" },
+            TokenKind::Comment {
+                text: "[CDATA[open",
+            },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn whitespace_tokens() {
+        let s = "<ul>\n  <li>a b</li>\n</ul> x ";
+        let options = TokenizerOptions {
+            whitespace_tokens: true,
+            ..Default::default()
+        };
+        let mut tokenizer = Tokenizer::with_options(s, options);
+        let expected_kinds = vec![
+            TokenKind::TagName { name: "ul" },
+            TokenKind::OpeningTagEnd,
+            TokenKind::Whitespace { text: "\n  " },
+            TokenKind::TagName { name: "li" },
+            TokenKind::OpeningTagEnd,
+            TokenKind::Text { text: "a b" },
+            TokenKind::TagEnd { name: "li" },
+            TokenKind::Whitespace { text: "\n" },
+            TokenKind::TagEnd { name: "ul" },
+            TokenKind::Text { text: " x " },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+
+        // off by default
+        let mut tokenizer = Tokenizer::new("<p> </p>");
+        tokenizer.next();
+        tokenizer.next();
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text { text: " " })
+        );
+    }
+
+    #[test]
+    fn processing_instruction() {
+        let s = "<?xml version=\"1.0\"?><?xml-stylesheet  type=\"text/xsl\" href=\"s.xsl\"?><p><?php echo 1 > 0;";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::ProcessingInstruction {
+                target: "xml",
+                data: "version=\"1.0\"",
+            },
+            TokenKind::ProcessingInstruction {
+                target: "xml-stylesheet",
+                data: "type=\"text/xsl\" href=\"s.xsl\"",
+            },
+            TokenKind::TagName { name: "p" },
+            TokenKind::OpeningTagEnd,
+            TokenKind::ProcessingInstruction {
+                target: "php",
+                data: "echo 1 > 0;",
+            },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let s = "<p><!-- héllo -- <b>";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.next();
+        tokenizer.next();
+        let comment = tokenizer.next().expect("comment");
+        assert_eq!(
+            comment.kind,
+            TokenKind::Comment {
+                text: " héllo -- <b>"
+            }
+        );
+        assert_eq!(comment.span.byte_range(), 7..s.len());
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn comment_with_multibyte_text() {
+        let s = "<!--é日本-->after";
+        let mut tokenizer = Tokenizer::new(s);
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Comment { text: "é日本" })
+        );
+        assert_eq!(
+            tokenizer.next().map(|t| t.kind),
+            Some(TokenKind::Text { text: "after" })
+        );
+    }
+
+    #[test]
+    fn text_ending_on_multibyte_character() {
+        let s = "<p data-ü=naïve>café";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.next(); // p
+        let name = tokenizer.next().expect("should exist");
+        assert_eq!(name.kind, TokenKind::AttributeName { name: "data-ü" });
+        assert_eq!(name.span.byte_range(), 3..10);
+        let value = tokenizer.next().expect("should exist");
+        assert_eq!(value.kind, TokenKind::AttributeValue { value: "naïve" });
+        assert_eq!(&s[value.span.byte_range()], "naïve");
+        tokenizer.next(); // >
+        let text = tokenizer.next().expect("should exist");
+        assert_eq!(text.kind, TokenKind::Text { text: "café" });
+        assert_eq!(text.span.byte_range(), 18..s.len());
+        assert_eq!(text.span.range.end.column, 20);
+    }
+
+    #[test]
+    fn end_tag_with_trailing_content() {
+        let s = "</p ></div\nclass=x>a</b";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::TagEnd { name: "p" },
+            TokenKind::TagEnd { name: "div" },
+            TokenKind::Text { text: "a" },
+            TokenKind::TagEnd { name: "b" },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn less_than_and_exclamation_mark_in_text() {
+        // `<!` always starts markup: what isn't a comment, doctype or CDATA
+        // section is a bogus comment up to the next `>`, while a `<` followed
+        // by anything but a letter, `/`, `!` or `?` is text
+        let s = "5 < 3 and a<!b> c <! d<!>e<!x";
+        let mut tokenizer = Tokenizer::new(s);
+        let expected_kinds = vec![
+            TokenKind::Text {
+                text: "5 < 3 and a",
+            },
+            TokenKind::Comment { text: "b" },
+            TokenKind::Text { text: " c " },
+            TokenKind::Comment { text: " d<!" },
+            TokenKind::Text { text: "e" },
+            TokenKind::Comment { text: "x" },
+        ];
+        for (i, k) in expected_kinds.into_iter().enumerate() {
+            let got = tokenizer.next().map(|g| g.kind);
+            assert_eq!((i, got), (i, Some(k)));
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn subspan_of_multiline_text() {
+        let s = "<p>first line\r\nsecond é line\nthird</p>";
+        let mut tokenizer = Tokenizer::new(s);
+        tokenizer.next(); // p
+        tokenizer.next(); // >
+        let text = tokenizer.next().expect("should exist");
+        let span = text.span();
+        let middle = span.subspan(6..21);
+        assert_eq!(middle.source(), "line\r\nsecond é");
+        assert_eq!(middle.byte_range(), 9..24);
+        assert_eq!(&s[middle.byte_range()], middle.source());
+        assert_eq!((middle.start(), middle.end()), ((0, 9), (1, 8)));
+
+        // spans nest, and an empty one is a point
+        let word = middle.subspan(13..15);
+        assert_eq!(word.source(), "é");
+        assert_eq!((word.start(), word.end()), ((1, 7), (1, 8)));
+        let end = span.subspan(span.source().len()..span.source().len());
+        assert_eq!(end.source(), "");
+        assert_eq!((end.start(), end.end()), ((2, 5), (2, 5)));
+    }
 }
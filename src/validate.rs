@@ -0,0 +1,164 @@
+//! Checks of a parsed tree against a few content-model rules of HTML, and for
+//! signs of mis-encoded input.
+
+use crate::{BLOCK_ELEMENTS, Diagnostic, Element, Namespace, Node, NodeKind, tokenizer::Span};
+
+/// Elements that must be children of one of a set of parents, with the
+/// diagnostic code and message for when they aren't.
+const REQUIRED_PARENTS: &[(&str, &[&str], &str, &str)] = &[
+    (
+        "li",
+        &["ul", "ol", "menu"],
+        "li-outside-list",
+        "`<li>` should be a child of `<ul>`, `<ol>` or `<menu>`",
+    ),
+    (
+        "td",
+        &["tr"],
+        "cell-outside-row",
+        "`<td>` should be a child of `<tr>`",
+    ),
+    (
+        "th",
+        &["tr"],
+        "cell-outside-row",
+        "`<th>` should be a child of `<tr>`",
+    ),
+    (
+        "option",
+        &["select", "datalist", "optgroup"],
+        "option-outside-select",
+        "`<option>` should be a child of `<select>`, `<datalist>` or `<optgroup>`",
+    ),
+];
+
+fn check<'a>(element: &Element<'a>, ancestors: &[&Element<'a>], found: &mut Vec<Diagnostic<'a>>) {
+    if element.namespace() != Namespace::Html {
+        return;
+    }
+    let parent = ancestors.last();
+    for (name, parents, code, message) in REQUIRED_PARENTS {
+        if element.is_named(name)
+            && !parent.is_some_and(|parent| parents.iter().any(|p| parent.is_named(p)))
+        {
+            found.push(Diagnostic::error(code, *message, element.start_tag()));
+        }
+    }
+    if BLOCK_ELEMENTS.iter().any(|b| element.is_named(b))
+        && ancestors.iter().any(|ancestor| ancestor.is_named("p"))
+    {
+        found.push(Diagnostic::error(
+            "block-in-paragraph",
+            format!(
+                "`<{}>` can't be inside `<p>`, which only holds phrasing content",
+                element.name()
+            ),
+            element.start_tag(),
+        ));
+    }
+}
+
+/// Check `nodes` for well-known nesting mistakes: `<li>` outside a list,
+/// `<td>` or `<th>` outside a `<tr>`, `<option>` outside a `<select>`, and
+/// block elements inside a `<p>`. Each mistake gives an error diagnostic
+/// pointing at the offending start tag.
+///
+/// This checks a handful of common rules, not the full content model.
+pub fn validate_nesting<'a>(nodes: &[Node<'a>]) -> Vec<Diagnostic<'a>> {
+    let mut found = vec![];
+    let mut ancestors = vec![];
+    let mut stack = nodes.iter().rev().map(|node| (node, 0)).collect::<Vec<_>>();
+    while let Some((node, depth)) = stack.pop() {
+        let NodeKind::Element(element) = &node.kind else {
+            continue;
+        };
+        ancestors.truncate(depth);
+        check(element, &ancestors, &mut found);
+        ancestors.push(element);
+        stack.extend(
+            element
+                .children
+                .iter()
+                .rev()
+                .map(|child| (child, depth + 1)),
+        );
+    }
+    found
+}
+
+/// The span of every U+FFFD replacement character in the text of `nodes`, in
+/// document order. Decoders put these in place of bytes that aren't valid in
+/// the encoding they assumed, so they usually point at an encoding problem
+/// upstream.
+pub fn find_replacement_chars<'a>(nodes: &[Node<'a>]) -> Vec<Span<'a>> {
+    const REPLACEMENT: &str = "\u{FFFD}";
+    let mut found = vec![];
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Text(token) | NodeKind::CData(token) => {
+                let span = token.span();
+                found.extend(
+                    span.source()
+                        .match_indices(REPLACEMENT)
+                        .map(|(i, _)| span.subspan(i..i + REPLACEMENT.len())),
+                );
+            }
+            NodeKind::Element(element) => found.extend(element.find_text(REPLACEMENT)),
+            _ => {}
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn nesting_violations() {
+        let html = "<body>
+<li>stray</li>
+<table><tr><td>ok</td></tr><td>loose</td></table>
+<p>text <div>block</div></p>
+<datalist><option>ok</option></datalist><option>alone</option>
+</body>";
+        let nodes = Parser::new(html).parse();
+        let found = validate_nesting(&nodes)
+            .iter()
+            .map(|d| (d.code(), d.span().source(), d.span().range().start.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("li-outside-list", "<li>", 1),
+                ("cell-outside-row", "<td>", 2),
+                ("block-in-paragraph", "<div>", 3),
+                ("option-outside-select", "<option>", 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_document() {
+        let html = "<ul><li>a</li></ul><ol><li><p>b</p></li></ol>
+            <table><tr><th>h</th><td><div>c</div></td></tr></table>
+            <select><optgroup><option>d</option></optgroup></select>
+            <p>e <b>f</b></p><svg><li/></svg>";
+        let nodes = Parser::new(html).parse();
+        assert!(validate_nesting(&nodes).is_empty());
+    }
+
+    #[test]
+    fn replacement_chars() {
+        let html =
+            "caf\u{FFFD} <p>na\u{FFFD}ve <b>\u{FFFD}\u{FFFD}</b></p><p title=\"\u{FFFD}\">fine</p>";
+        let nodes = Parser::new(html).parse();
+        let found = find_replacement_chars(&nodes)
+            .iter()
+            .map(|span| (span.byte_range().start, span.range().start.column))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(3, 3), (12, 10), (21, 17), (24, 18)]);
+        assert!(find_replacement_chars(&Parser::new("<p>café</p>").parse()).is_empty());
+    }
+}